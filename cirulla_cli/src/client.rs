@@ -0,0 +1,91 @@
+mod shuffle;
+
+use cirulla_lib::Seed;
+use shuffle::ShuffleVerifier;
+use std::io::Error;
+use tokio::{
+    io::{split, stdin, stdout, AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader},
+    net::TcpStream,
+    runtime::Runtime,
+    select,
+};
+use tokio_rustls::{rustls::pki_types::ServerName, TlsConnector};
+
+/// Connects to a server and relays the protocol lines between the terminal
+/// and the server, answering the heartbeat on behalf of the user and
+/// checking that every hand is shuffled as the server committed to.
+pub fn start_client(address: String, port: u16, tls: Option<TlsConnector>) {
+    let runtime = Runtime::new().expect("Failed to start the async runtime");
+    if let Err(e) = runtime.block_on(connect(address, port, tls)) {
+        eprintln!("Connection error: {}", e);
+    }
+    // Reading from stdin blocks a thread that would keep the runtime alive
+    runtime.shutdown_background();
+}
+
+async fn connect(address: String, port: u16, tls: Option<TlsConnector>) -> Result<(), Error> {
+    let stream = TcpStream::connect((address.as_str(), port)).await?;
+
+    match tls {
+        Some(connector) => {
+            let name = ServerName::try_from(address).map_err(Error::other)?;
+            relay(connector.connect(name, stream).await?).await
+        }
+        None => relay(stream).await,
+    }
+}
+
+async fn relay<S>(stream: S) -> Result<(), Error>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let (reader, mut writer) = split(stream);
+    let mut server_lines = BufReader::new(reader).lines();
+    let mut user_lines = BufReader::new(stdin()).lines();
+    let mut output = stdout();
+    let mut verifier = ShuffleVerifier::default();
+
+    loop {
+        select! {
+            line = server_lines.next_line() => match line? {
+                Some(line) if line == "PING" => {
+                    writer.write_all(b"PONG\n").await?;
+                }
+                Some(line) => {
+                    output.write_all(format!("{}\n", line).as_bytes()).await?;
+                    if let Some(verdict) = verifier.observe(&line) {
+                        output.write_all(format!("{}\n", verdict).as_bytes()).await?;
+                    }
+                    if line.starts_with("SEED COMMIT ") {
+                        // Our own share of the next shuffle
+                        let entropy = Seed::random().to_string();
+                        verifier.sent_entropy(&entropy);
+                        writer
+                            .write_all(format!("ENTROPY {}\n", entropy).as_bytes())
+                            .await?;
+                    }
+                    output.flush().await?;
+                }
+                None => break,
+            },
+            line = user_lines.next_line() => match line? {
+                Some(line) => {
+                    // Typed by hand it replaces the one sent on our behalf
+                    let mut words = line.split_whitespace();
+                    if words.next().is_some_and(|w| w.eq_ignore_ascii_case("entropy")) {
+                        if let Some(entropy) = words.next() {
+                            verifier.sent_entropy(entropy);
+                        }
+                    }
+                    writer.write_all(format!("{}\n", line).as_bytes()).await?;
+                }
+                None => {
+                    writer.write_all(b"QUIT\n").await?;
+                    break;
+                }
+            },
+        }
+    }
+
+    Ok(())
+}
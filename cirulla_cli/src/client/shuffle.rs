@@ -0,0 +1,238 @@
+use cirulla_lib::{Game, Seed};
+use serde_json::Value;
+
+/// Follows the game from the lines sent by the server and, when a hand is
+/// over, replays its deal from the revealed seed to check that the server
+/// dealt the cards it committed to.
+#[derive(Default)]
+pub struct ShuffleVerifier {
+    name: Option<String>,
+    commitment: Option<String>,
+    /// Entropy sent for the committed seed while a hand was being played,
+    /// so surely before the server deals from it
+    entropy: Option<String>,
+    status: Option<String>,
+    hand: Option<HandSeen>,
+}
+
+/// What the player saw of a hand since it was dealt.
+struct HandSeen {
+    commitment: Option<String>,
+    entropy: Option<String>,
+    seat: usize,
+    players: usize,
+    table: Vec<String>,
+    cards: Vec<String>,
+}
+
+impl ShuffleVerifier {
+    /// Looks at a line from the server, giving back what the user should be
+    /// told about the fairness of the shuffle, if anything.
+    pub fn observe(&mut self, line: &str) -> Option<String> {
+        if let Some(status) = self.status.as_mut() {
+            if line == "GAME STATUS END" {
+                let status = self.status.take().unwrap_or_default();
+                if let Ok(status) = serde_json::from_str(&status) {
+                    self.see_status(&status);
+                }
+            } else {
+                status.push_str(line);
+            }
+            return None;
+        }
+
+        if line == "GAME STATUS START" {
+            self.status = Some(String::new());
+        } else if let Some(name) = line.strip_prefix("HI ") {
+            self.name = Some(name.to_string());
+        } else if let Some(commitment) = line.strip_prefix("SEED COMMIT ") {
+            self.commitment = Some(commitment.to_string());
+            self.entropy = None;
+        } else if let Some(reveal) = line.strip_prefix("SEED REVEAL ") {
+            return self.verify(reveal);
+        }
+        None
+    }
+
+    /// Remembers the entropy this client sent, which the server has to mix
+    /// into the next hand. Entropy sent before a hand is in play may reach
+    /// the server after it already dealt, and is not checked.
+    pub fn sent_entropy(&mut self, entropy: &str) {
+        if self.hand.is_some() {
+            self.entropy = Some(entropy.to_string());
+        }
+    }
+
+    fn see_status(&mut self, status: &Value) {
+        let players = status["players"].as_array().cloned().unwrap_or_default();
+        let cards = |value: &Value| -> Vec<String> {
+            value
+                .as_array()
+                .map(|cards| {
+                    cards
+                        .iter()
+                        .filter_map(|card| card.as_str().map(str::to_string))
+                        .collect()
+                })
+                .unwrap_or_default()
+        };
+
+        // Right after the deal everybody has three cards and the deck has
+        // lost the four on the table too
+        let just_dealt = status["cards_in_deck"].as_u64()
+            == Some(40 - 4 - 3 * players.len() as u64)
+            && players.iter().all(|p| p["hand_size"].as_u64() == Some(3));
+        if just_dealt {
+            let seat = players
+                .iter()
+                .position(|p| p["name"].as_str() == self.name.as_deref());
+            self.hand = seat.map(|seat| HandSeen {
+                commitment: self.commitment.clone(),
+                entropy: self.entropy.take(),
+                seat,
+                players: players.len(),
+                table: cards(&status["cards_on_table"]),
+                cards: cards(&status["hand"]),
+            });
+        } else if let Some(hand) = self.hand.as_mut() {
+            for card in cards(&status["hand"]) {
+                if !hand.cards.contains(&card) {
+                    hand.cards.push(card);
+                }
+            }
+        }
+    }
+
+    fn verify(&mut self, reveal: &str) -> Option<String> {
+        // Hands joined halfway cannot be checked
+        let hand = self.hand.take()?;
+        let mut parts = reveal.split_whitespace();
+        let seed = match parts.next().and_then(|seed| seed.parse::<Seed>().ok()) {
+            Some(seed) => seed,
+            None => return Some("WARNING: the server revealed an invalid seed".to_string()),
+        };
+        let entropy: Vec<String> = parts.map(str::to_string).collect();
+
+        if let Some(sent) = hand.entropy {
+            if !entropy.contains(&sent) {
+                return Some(
+                    "WARNING: the server left out the entropy sent by this client".to_string(),
+                );
+            }
+        }
+
+        if hand.commitment != Some(seed.commitment()) {
+            return Some(
+                "WARNING: the revealed seed is not the one the server committed to".to_string(),
+            );
+        }
+
+        let deal = match Game::replay_deal(&seed.mix(&entropy), hand.players) {
+            Ok(deal) => deal,
+            Err(e) => return Some(format!("WARNING: cannot replay the deal: {}", e)),
+        };
+        let mut expected: Vec<String> = deal.hands[hand.seat]
+            .iter()
+            .map(|c| c.to_string())
+            .collect();
+        let mut received = hand.cards;
+        expected.sort();
+        received.sort();
+        let table: Vec<String> = deal.table.iter().map(|c| c.to_string()).collect();
+        if table != hand.table || expected != received {
+            return Some("WARNING: the cards dealt do not match the revealed seed".to_string());
+        }
+
+        Some("Shuffle verified: the cards dealt match the revealed seed".to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn status_lines(game: &Game) -> Vec<String> {
+        let status = game.as_game_for_player(&game.players[1].id);
+        let mut lines = vec!["GAME STATUS START".to_string()];
+        lines.extend(
+            serde_json::to_string_pretty(&status)
+                .unwrap()
+                .lines()
+                .map(str::to_string),
+        );
+        lines.push("GAME STATUS END".to_string());
+        lines
+    }
+
+    /// Deals a hand from `seed` and plays it out as Bepi; once it is dealt,
+    /// the seed of the next hand is committed to and `entropy` sent for it.
+    fn play_hand(verifier: &mut ShuffleVerifier, seed: &Seed, next: &Seed, entropy: &str) {
+        let mut game = Game::new(51);
+        game.add_player("Anna", None).unwrap();
+        game.add_player("Bepi", None).unwrap();
+        game.start_game().unwrap();
+        game.start_hand_with_seed(seed).unwrap();
+        let mut dealt = false;
+        while !game.deck.is_empty() {
+            game.start_round().unwrap();
+            for line in status_lines(&game) {
+                verifier.observe(&line);
+            }
+            if !dealt {
+                verifier.observe(&format!("SEED COMMIT {}", next.commitment()));
+                verifier.sent_entropy(entropy);
+                dealt = true;
+            }
+            for player in game.players.iter_mut() {
+                player.hand.clear();
+            }
+        }
+    }
+
+    /// Plays two hands as Bepi, giving back the verdict on the second one
+    /// as revealed by the server.
+    fn verdict(committed: &Seed, revealed: &Seed, revealed_entropy: &str) -> Option<String> {
+        let mut verifier = ShuffleVerifier::default();
+        verifier.observe("HI Bepi");
+        let first = Seed::random();
+        verifier.observe(&format!("SEED COMMIT {}", first.commitment()));
+
+        // Bepi's entropy for the first hand may come too late, so none is sent
+        play_hand(&mut verifier, &first.mix(&[]), committed, "c0ffee");
+        let first_verdict = verifier.observe(&format!("SEED REVEAL {}", first)).unwrap();
+        assert!(
+            first_verdict.starts_with("Shuffle verified"),
+            "{}",
+            first_verdict
+        );
+
+        let entropy = vec!["c0ffee".to_string()];
+        play_hand(
+            &mut verifier,
+            &committed.mix(&entropy),
+            &Seed::random(),
+            "f00d",
+        );
+        verifier.observe(&format!("SEED REVEAL {} {}", revealed, revealed_entropy))
+    }
+
+    #[test]
+    fn an_honest_deal_is_verified() {
+        let seed = Seed::random();
+        let verdict = verdict(&seed, &seed, "c0ffee").unwrap();
+        assert!(verdict.starts_with("Shuffle verified"), "{}", verdict);
+    }
+
+    #[test]
+    fn a_different_seed_is_caught() {
+        let verdict = verdict(&Seed::random(), &Seed::random(), "c0ffee").unwrap();
+        assert!(verdict.starts_with("WARNING"), "{}", verdict);
+    }
+
+    #[test]
+    fn entropy_left_out_is_caught() {
+        let seed = Seed::random();
+        let verdict = verdict(&seed, &seed, "abad1dea").unwrap();
+        assert!(verdict.contains("left out the entropy"), "{}", verdict);
+    }
+}
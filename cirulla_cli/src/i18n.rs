@@ -0,0 +1,344 @@
+//! The texts shown to people, in every language Cirulla speaks: the local
+//! game reads them from [Texts], the server uses [Lang] to pick the one of
+//! each session.
+
+use cirulla_lib::{Effect, GameError};
+use clap::ValueEnum;
+use std::{env, fmt::Display};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum Lang {
+    /// Italiano
+    #[default]
+    It,
+    /// English
+    En,
+}
+
+impl Lang {
+    /// The language of the locale in the environment, Italian when it is
+    /// not set or not one Cirulla speaks.
+    pub fn from_locale() -> Lang {
+        ["LC_ALL", "LC_MESSAGES", "LANG"]
+            .iter()
+            .filter_map(|name| env::var(name).ok())
+            .find(|value| !value.is_empty())
+            .and_then(|locale| Lang::from_code(&locale))
+            .unwrap_or_default()
+    }
+
+    /// The language of a code such as `en` or a locale such as `en_GB.UTF-8`.
+    pub fn from_code(code: &str) -> Option<Lang> {
+        let language = code.split(['_', '.', '-', '@']).next().unwrap_or_default();
+        Lang::from_str(language, true).ok()
+    }
+
+    pub fn texts(self) -> &'static Texts {
+        match self {
+            Lang::It => &ITALIAN,
+            Lang::En => &ENGLISH,
+        }
+    }
+}
+
+impl Display for Lang {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Lang::It => write!(f, "it"),
+            Lang::En => write!(f, "en"),
+        }
+    }
+}
+
+/// The catalog of a language. Texts taking values are functions, so that
+/// every language can put them where its grammar wants.
+pub struct Texts {
+    pub new_hand: fn(&str) -> String,
+    pub effect: fn(&Effect) -> String,
+    pub plays: fn(&str, &str) -> String,
+    pub takes: fn(&str) -> String,
+    pub broom: &'static str,
+    pub last_take: fn(&str, &str) -> String,
+    pub too_small: &'static str,
+    pub needed_size: fn(u16, u16, u16, u16) -> String,
+    pub widen_or_quit: &'static str,
+    pub cards: &'static str,
+    pub diamonds: &'static str,
+    pub primiera: &'static str,
+    pub pretty_seven: &'static str,
+    pub high_ladder: &'static str,
+    pub low_ladder: &'static str,
+    pub points: &'static str,
+    pub press_to_continue: fn(char) -> String,
+    pub capture_of: fn(usize, usize) -> String,
+    pub capture_keys: &'static str,
+    pub deck_left: fn(usize) -> String,
+    pub win_at: fn(u8) -> String,
+    pub log_title: &'static str,
+    pub dealer: &'static str,
+    pub player_stats: fn(usize, u8, u8, &str) -> String,
+    pub hand_over: fn(&str) -> String,
+    pub final_ranking: &'static str,
+    pub turn: fn(&str) -> String,
+    pub score: fn(&str, u8, u8, usize) -> String,
+    pub table_status: fn(&str, usize, u8) -> String,
+    pub hand_over_plain: fn(&str) -> String,
+    pub empty: &'static str,
+    pub table_and_hand: fn(&str, &str) -> String,
+    pub table_cards: fn(&str) -> String,
+    pub shows: fn(&str, &str) -> String,
+    pub card_to_play: &'static str,
+    pub choose: fn(&str, usize, usize) -> String,
+    pub write_a_number: fn(usize, usize) -> String,
+    pub possible_captures: &'static str,
+    pub capture_to_make: &'static str,
+    pub nobody: &'static str,
+    pub hand_end: &'static str,
+    pub press_enter: &'static str,
+    pub game_error: fn(&GameError) -> String,
+    pub help_hint: &'static str,
+    pub help_close: &'static str,
+    pub controls_title: &'static str,
+    pub controls: &'static [&'static str],
+    pub rules_title: &'static str,
+    pub rule_capture: &'static str,
+    pub rule_fifteen: &'static str,
+    pub rule_ace_sweep: &'static str,
+    pub rule_knocking: &'static str,
+    pub rule_points: &'static str,
+    pub rule_primiera: &'static str,
+    pub rule_high_ladder: &'static str,
+    pub rule_low_ladder: &'static str,
+}
+
+impl Texts {
+    /// A summary of the rules, playing to `win_at` points.
+    pub fn rules(&self, win_at: u8) -> Vec<String> {
+        [
+            self.rule_capture,
+            self.rule_fifteen,
+            self.rule_ace_sweep,
+            self.rule_knocking,
+            self.rule_points,
+            self.rule_primiera,
+            self.rule_high_ladder,
+            self.rule_low_ladder,
+        ]
+        .iter()
+        .map(|text| text.to_string())
+        .chain([(self.win_at)(win_at)])
+        .collect()
+    }
+}
+
+static ITALIAN: Texts = Texts {
+    new_hand: |dealer| format!("Nuova mano, mazzo a {}", dealer),
+    effect: |effect| match effect {
+        Effect::DeckHandlerBroom(value) => format!("Banco a {}", value),
+        Effect::Knocked(value) => format!("Bussa da {}", value),
+    },
+    plays: |name, card| format!("{} gioca {}", name, card),
+    takes: |cards| format!(", prende {}", cards),
+    broom: " — SCOPA",
+    last_take: |name, cards| format!("{} prende le ultime: {}", name, cards),
+    too_small: "Il terminale è troppo piccolo",
+    needed_size: |columns, rows, now_columns, now_rows| {
+        format!(
+            "Servono almeno {}x{} caratteri, ora sono {}x{}",
+            columns, rows, now_columns, now_rows
+        )
+    },
+    widen_or_quit: "Allarga la finestra o premi `q` per uscire",
+    cards: "Carte:",
+    diamonds: "Denari:",
+    primiera: "Primiera:",
+    pretty_seven: "Settebello:",
+    high_ladder: "Alta:",
+    low_ladder: "Bassa:",
+    points: "Punti:",
+    press_to_continue: |key| format!("Premi `{}` per continuare...", key),
+    capture_of: |index, count| format!("Presa {} di {}: ←/→ o clic", index, count),
+    capture_keys: "invio conferma, Esc annulla",
+    deck_left: |cards| format!("Carte nel mazzo: {}", cards),
+    win_at: |points| format!("Si vince a {} punti", points),
+    log_title: " Partita ",
+    dealer: "MAZZO",
+    player_stats: |cards, brooms, points, sep| {
+        format!("Carte: {cards}{sep}Scope: {brooms}{sep}Punti: {points}")
+    },
+    hand_over: |name| format!("Passa a {}, poi premi spazio", name),
+    final_ranking: "Classifica finale",
+    turn: |name| format!("Tocca a {}.", name),
+    score: |name, points, brooms, cards| {
+        format!(
+            "{} {} punti, {} scope, {} carte",
+            name, points, brooms, cards
+        )
+    },
+    table_status: |scores, deck, win_at| {
+        format!(
+            "{}; carte nel mazzo: {}, si vince a {} punti.",
+            scores, deck, win_at
+        )
+    },
+    hand_over_plain: |name| format!("Passa a {}, poi premi invio.", name),
+    empty: "vuoto",
+    table_and_hand: |table, hand| format!("Tavolo: {}; la tua mano: {}", table, hand),
+    table_cards: |table| format!("Tavolo: {}.", table),
+    shows: |name, cards| format!("{} mostra: {}", name, cards),
+    card_to_play: "Carta da giocare",
+    choose: |prompt, min, max| format!("{} ({}-{}, q per uscire): ", prompt, min, max),
+    write_a_number: |min, max| format!("Scrivi un numero da {} a {}.", min, max),
+    possible_captures: "Prese possibili",
+    capture_to_make: "Presa da fare, 0 per cambiare carta",
+    nobody: "nessuno",
+    hand_end: "Fine della mano.",
+    press_enter: "Premi invio per continuare.",
+    game_error: |error| {
+        match error {
+            GameError::GameAlreadyStarted => "Partita già iniziata",
+            GameError::TooManyPlayers => "Troppi giocatori",
+            GameError::NameTooShort => "Nome troppo corto",
+            GameError::NameAlreadyTaken => "Nome già preso",
+            GameError::NotEnoughPlayers => "Giocatori insufficienti",
+            GameError::GameNotStarted => "Partita non iniziata",
+            GameError::DeckNotReady => "Mazzo non pronto",
+            GameError::HandNotStarted => "Mano non iniziata",
+            GameError::CardNotFound => "Carta non trovata",
+            GameError::InvalidSeed => "Seed non valido",
+            GameError::InvalidCapture => "Presa non valida",
+        }
+        .to_string()
+    },
+    help_hint: "`?` per l'aiuto",
+    help_close: "Premi un tasto per tornare alla partita",
+    controls_title: "Comandi",
+    controls: &[
+        "←/→ o clic: sceglie la carta da giocare",
+        "invio o spazio: gioca la carta scelta",
+        "←/→ o clic sul tavolo: sceglie la presa, invio la conferma, Esc cambia carta",
+        "q: esce dal gioco",
+    ],
+    rules_title: "Regole",
+    rule_capture: "Presa: una carta prende quelle che sommano il suo valore, il più possibile; se prende tutto il tavolo è scopa",
+    rule_fifteen: "Presa di 15: una carta prende anche quelle che sommate a lei fanno 15",
+    rule_ace_sweep: "Scopa d'assi: l'asso prende tutto il tavolo se non c'è già un asso",
+    rule_knocking: "Bussare: chi riceve tre carte uguali segna 10 scope, chi riceve al massimo 9 punti ne segna 3; in entrambi i casi mostra la mano",
+    rule_points: "A fine mano un punto ciascuno per più carte, più denari, settebello e ogni scopa",
+    rule_primiera: "Primiera: un punto a chi somma di più con la carta migliore di ogni seme (7, 6, asso, 5...)",
+    rule_high_ladder: "Grande: fante, cavallo e re di denari valgono 5 punti",
+    rule_low_ladder: "Piccola: asso, 2 e 3 di denari valgono 3 punti, più uno per ogni carta di denari che segue",
+};
+
+static ENGLISH: Texts = Texts {
+    new_hand: |dealer| format!("New hand, {} deals", dealer),
+    effect: |effect| match effect {
+        Effect::DeckHandlerBroom(value) => format!("Dealer's {}", value),
+        Effect::Knocked(value) => format!("Knocks with {}", value),
+    },
+    plays: |name, card| format!("{} plays {}", name, card),
+    takes: |cards| format!(", takes {}", cards),
+    broom: " — SWEEP",
+    last_take: |name, cards| format!("{} takes the last cards: {}", name, cards),
+    too_small: "The terminal is too small",
+    needed_size: |columns, rows, now_columns, now_rows| {
+        format!(
+            "At least {}x{} characters are needed, now there are {}x{}",
+            columns, rows, now_columns, now_rows
+        )
+    },
+    widen_or_quit: "Widen the window or press `q` to quit",
+    cards: "Cards:",
+    diamonds: "Diamonds:",
+    primiera: "Primiera:",
+    pretty_seven: "Settebello:",
+    high_ladder: "High run:",
+    low_ladder: "Low run:",
+    points: "Points:",
+    press_to_continue: |key| format!("Press `{}` to continue...", key),
+    capture_of: |index, count| format!("Capture {} of {}: ←/→ or click", index, count),
+    capture_keys: "Enter confirms, Esc cancels",
+    deck_left: |cards| format!("Cards in the deck: {}", cards),
+    win_at: |points| format!("Playing to {} points", points),
+    log_title: " Game ",
+    dealer: "DECK",
+    player_stats: |cards, brooms, points, sep| {
+        format!("Cards: {cards}{sep}Sweeps: {brooms}{sep}Score: {points}")
+    },
+    hand_over: |name| format!("Pass to {}, then press space", name),
+    final_ranking: "Final standings",
+    turn: |name| format!("{}'s turn.", name),
+    score: |name, points, brooms, cards| {
+        format!(
+            "{} {} points, {} sweeps, {} cards",
+            name, points, brooms, cards
+        )
+    },
+    table_status: |scores, deck, win_at| {
+        format!(
+            "{}; cards in the deck: {}, playing to {} points.",
+            scores, deck, win_at
+        )
+    },
+    hand_over_plain: |name| format!("Pass to {}, then press Enter.", name),
+    empty: "empty",
+    table_and_hand: |table, hand| format!("Table: {}; your hand: {}", table, hand),
+    table_cards: |table| format!("Table: {}.", table),
+    shows: |name, cards| format!("{} shows: {}", name, cards),
+    card_to_play: "Card to play",
+    choose: |prompt, min, max| format!("{} ({}-{}, q to quit): ", prompt, min, max),
+    write_a_number: |min, max| format!("Write a number from {} to {}.", min, max),
+    possible_captures: "Possible captures",
+    capture_to_make: "Capture to make, 0 to change card",
+    nobody: "nobody",
+    hand_end: "End of the hand.",
+    press_enter: "Press Enter to continue.",
+    game_error: |error| error.to_string(),
+    help_hint: "`?` for help",
+    help_close: "Press a key to go back to the game",
+    controls_title: "Controls",
+    controls: &[
+        "←/→ or click: choose the card to play",
+        "Enter or space: play the chosen card",
+        "←/→ or click on the table: choose the capture, Enter confirms it, Esc changes card",
+        "q: quit the game",
+    ],
+    rules_title: "Rules",
+    rule_capture: "Capture: a card takes the cards adding up to its value, as many as possible; taking the whole table is a sweep",
+    rule_fifteen: "15-capture: a card also takes the cards adding up to 15 with it",
+    rule_ace_sweep: "Scopa d'assi: an ace takes the whole table when there is no ace on it",
+    rule_knocking: "Knocking: who is dealt three equal cards scores 10 sweeps, who is dealt no more than 9 points scores 3; either way the hand is shown",
+    rule_points: "At the end of the hand a point each for most cards, most diamonds, the seven of diamonds and every sweep",
+    rule_primiera: "Primiera: a point for the highest sum of the best card of every suit (7, 6, ace, 5...)",
+    rule_high_ladder: "Grande: the J, Q and K of diamonds are worth 5 points",
+    rule_low_ladder: "Piccola: the ace, 2 and 3 of diamonds are worth 3 points, and one more for every following diamond",
+};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn locales_choose_the_language() {
+        assert_eq!(Lang::from_code("en_GB.UTF-8"), Some(Lang::En));
+        assert_eq!(Lang::from_code("it_IT@euro"), Some(Lang::It));
+        assert_eq!(Lang::from_code("EN"), Some(Lang::En));
+        assert_eq!(Lang::from_code("de_DE.UTF-8"), None);
+        assert_eq!(Lang::from_code("C"), None);
+    }
+
+    #[test]
+    fn english_game_errors_are_the_library_ones() {
+        let error = GameError::InvalidCapture;
+        assert_eq!((Lang::En.texts().game_error)(&error), error.to_string());
+        assert_eq!((Lang::It.texts().game_error)(&error), "Presa non valida");
+    }
+
+    #[test]
+    fn the_rules_end_with_the_points_to_win() {
+        let texts = Lang::En.texts();
+        let rules = texts.rules(51);
+        assert!(rules.iter().any(|rule| rule == texts.rule_knocking));
+        assert_eq!(rules.last().unwrap(), "Playing to 51 points");
+    }
+}
@@ -0,0 +1,6 @@
+//! The network side of Cirulla: the game server and the TLS helpers shared
+//! with the client, along with the texts in every language.
+
+pub mod i18n;
+pub mod server;
+pub mod tls;
@@ -1,22 +1,109 @@
-use cirulla_lib::{Game, NextAction};
+use cirulla_cli::server::ConfigError;
+use cirulla_lib::{Bot, Card, Game, GameError, Greedy, Mcts, NextAction};
+use clap::ValueEnum;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::time::Duration;
+use std::{fs, path::Path, thread};
 
-use crate::ui::UI;
+use crate::ui::{Frontend, Theme};
+
+/// How long a bot seems to think, so that its move can be followed.
+const BOT_DELAY: Duration = Duration::from_millis(800);
+
+/// The strategies a computer player can follow.
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum, Debug)]
+pub enum BotKind {
+    /// Takes as much as it can right away
+    Greedy,
+    /// Plays out many possible hands before choosing
+    Mcts,
+}
+
+impl BotKind {
+    fn create(self) -> Box<dyn Bot> {
+        match self {
+            BotKind::Greedy => Box::new(Greedy),
+            BotKind::Mcts => Box::new(Mcts::default()),
+        }
+    }
+}
+
+/// What the local game can be configured with, as read from a TOML file.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LocalConfig {
+    pub theme: Theme,
+}
+
+impl LocalConfig {
+    pub fn from_file(path: &Path) -> Result<LocalConfig, ConfigError> {
+        let content =
+            fs::read_to_string(path).map_err(|e| ConfigError::Read(path.to_path_buf(), e))?;
+        toml::from_str(&content).map_err(|e| ConfigError::Parse(path.to_path_buf(), e))
+    }
+}
 
 pub struct LocalGame {
     game: Game,
-    ui: UI,
+    ui: Box<dyn Frontend>,
+    bots: HashMap<String, Box<dyn Bot>>,
 }
 
 impl LocalGame {
-    pub fn new(players: &Vec<String>, win_at: u8) -> LocalGame {
+    /// Seats the players and then the bots, failing when they are too many
+    /// or a name is not valid.
+    pub fn new(
+        ui: Box<dyn Frontend>,
+        players: &[String],
+        bots: &[BotKind],
+        win_at: u8,
+    ) -> Result<LocalGame, GameError> {
         let mut game = Game::new(win_at);
-        players.iter().for_each(|name| {
-            game.add_player(name, None).unwrap();
-        });
-        LocalGame {
-            game,
-            ui: UI::new(),
+        for name in players {
+            game.add_player(name, None)?;
+        }
+        let bots = bots
+            .iter()
+            .enumerate()
+            .map(|(i, kind)| {
+                let strategy = kind.to_possible_value().expect("Not skipped");
+                let name = format!("Bot {} ({})", i + 1, strategy.get_name());
+                Ok((game.add_player(&name, None)?, kind.create()))
+            })
+            .collect::<Result<_, GameError>>()?;
+        Ok(LocalGame { game, ui, bots })
+    }
+
+    /// Which hands can be shown: a human's own while it is their turn, or
+    /// always when they are the only human at the table.
+    fn face_up(&self) -> Vec<bool> {
+        let humans = self.game.players.len() - self.bots.len();
+        self.game
+            .players
+            .iter()
+            .enumerate()
+            .map(|(i, p)| {
+                !self.bots.contains_key(&p.id)
+                    && (humans == 1 || i == self.game.current_player_index)
+            })
+            .collect()
+    }
+
+    /// Plays `card` for the current player taking `capture`, or what the
+    /// rules take when it is `None`.
+    fn play(&mut self, card: &str, capture: Option<Vec<Card>>) {
+        match capture {
+            Some(capture) => self.game.player_play_capturing(card, &capture).unwrap(),
+            None => self.game.player_play(card).unwrap(),
         }
+        self.show_events();
+    }
+
+    /// Tells the players what happened since the last time.
+    fn show_events(&mut self) {
+        let events = self.game.take_events();
+        self.ui.show_events(&self.game, &events).unwrap();
     }
 
     pub fn start(&mut self) {
@@ -31,10 +118,28 @@ impl LocalGame {
             self.game.start_hand().unwrap();
             'hand: loop {
                 self.game.start_round().unwrap();
+                self.show_events();
                 'round: loop {
-                    self.ui.draw_table(&self.game);
-                    let card = self.ui.ask_for_card(&self.game).unwrap();
-                    self.game.player_play(&card).unwrap();
+                    let current = self.game.current_player();
+                    let human = !self.bots.contains_key(&current.id);
+                    if human && self.game.players.len() - self.bots.len() > 1 {
+                        let name = current.name.clone();
+                        self.ui.hand_over(&self.game, &name).unwrap();
+                    }
+
+                    let face_up = self.face_up();
+                    self.ui.draw_table(&self.game, &face_up).unwrap();
+                    let (card, capture) = match self.bots.get_mut(&self.game.current_player().id) {
+                        Some(bot) => {
+                            thread::sleep(BOT_DELAY);
+                            (bot.choose_card(&self.game), None)
+                        }
+                        None => {
+                            let (card, capture) = self.ui.ask_for_card(&self.game).unwrap();
+                            (card, Some(capture))
+                        }
+                    };
+                    self.play(&card, capture);
                     let next_action = self.game.next_round_action();
                     match next_action {
                         NextAction::NextPlayer => {
@@ -44,6 +149,7 @@ impl LocalGame {
                             continue 'hand;
                         }
                         NextAction::EndHand => {
+                            self.show_events();
                             let result = self.game.end_hand().unwrap();
                             self.ui
                                 .show_hand_result(&result, &self.game.players)
@@ -62,3 +168,33 @@ impl LocalGame {
         self.ui.draw_winner(&self.game).unwrap();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ui::PlainUI;
+    use cirulla_cli::i18n::Lang;
+    use std::io::Cursor;
+
+    fn plain_ui() -> Box<dyn Frontend> {
+        Box::new(PlainUI::with(
+            Cursor::new(Vec::new()),
+            Vec::new(),
+            Lang::It,
+            Theme::default(),
+        ))
+    }
+
+    #[test]
+    fn seats_are_limited_with_bots_too() {
+        let players = vec!["Anna".to_string(), "Bepi".to_string()];
+        let bots = [BotKind::Greedy, BotKind::Greedy];
+        assert!(LocalGame::new(plain_ui(), &players, &bots, 51).is_ok());
+
+        let bots = [BotKind::Greedy, BotKind::Mcts, BotKind::Greedy];
+        assert!(matches!(
+            LocalGame::new(plain_ui(), &players, &bots, 51),
+            Err(GameError::TooManyPlayers)
+        ));
+    }
+}
@@ -1,10 +1,15 @@
+mod client;
 mod local;
-mod server;
 mod ui;
 
+use cirulla_cli::i18n::Lang;
+use cirulla_cli::server::{start_service, HeartbeatConfig, ServerConfig, TlsFiles};
+use cirulla_cli::tls;
 use clap::{Parser, ValueEnum};
-use local::LocalGame;
-use server::start_service;
+use client::start_client;
+use local::{BotKind, LocalConfig, LocalGame};
+use std::path::PathBuf;
+use ui::{Frontend, Palette, PlainUI, Suits, UI};
 
 #[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum, Debug)]
 enum Mode {
@@ -23,6 +28,11 @@ struct Args {
     #[arg(value_enum, default_value_t = Mode::Local)]
     mode: Mode,
 
+    /// TOML file configuring the server, whose other options are then
+    /// ignored (Server mode), or the theme of the game (Local mode)
+    #[arg(short, long)]
+    config: Option<PathBuf>,
+
     /// The address to listen on (Server mode) or connect to (Client mode)
     #[arg(short, long, default_value = "localhost")]
     address: String,
@@ -35,9 +45,62 @@ struct Args {
     #[arg(short, long)]
     name: Vec<String>,
 
+    /// A computer player to seat, with the strategy it follows (Local mode)
+    #[arg(short, long, value_enum)]
+    bot: Vec<BotKind>,
+
     /// The number of points to play to
     #[arg(short, long, default_value_t = 51)]
     win_at: u8,
+
+    /// Play a line at a time on plain text, for screen readers and pipes (Local mode)
+    #[arg(long)]
+    plain: bool,
+
+    /// The language to play in; by default the one of the locale (Local mode)
+    #[arg(long, value_enum)]
+    lang: Option<Lang>,
+
+    /// Draw with ASCII characters only (Local mode)
+    #[arg(long)]
+    ascii: bool,
+
+    /// The colours of the cards (Local mode)
+    #[arg(long, value_enum)]
+    palette: Option<Palette>,
+
+    /// How the suits are named (Local mode)
+    #[arg(long, value_enum)]
+    suits: Option<Suits>,
+
+    /// Seconds between two PING sent to each client (Server mode)
+    #[arg(long, value_parser = clap::value_parser!(u64).range(1..), default_value_t = 30)]
+    ping_interval: u64,
+
+    /// Seconds of silence after which a client is disconnected (Server mode)
+    #[arg(long, value_parser = clap::value_parser!(u64).range(1..), default_value_t = 90)]
+    idle_timeout: u64,
+
+    /// Seconds allowed to deliver a message to a client (Server mode)
+    #[arg(long, value_parser = clap::value_parser!(u64).range(1..), default_value_t = 10)]
+    write_timeout: u64,
+
+    /// PEM certificate chain enabling TLS on the listener (Server mode)
+    #[arg(long, requires = "tls_key")]
+    tls_cert: Option<PathBuf>,
+
+    /// PEM private key of the TLS certificate (Server mode)
+    #[arg(long, requires = "tls_cert")]
+    tls_key: Option<PathBuf>,
+
+    /// Connect using TLS (Client mode)
+    #[arg(long)]
+    tls: bool,
+
+    /// PEM certificate to trust besides the well known authorities, for
+    /// servers using a self-signed certificate; implies --tls (Client mode)
+    #[arg(long)]
+    tls_ca: Option<PathBuf>,
 }
 
 fn main() {
@@ -46,14 +109,89 @@ fn main() {
 
     match args.mode {
         Mode::Server => {
-            start_service(args.address, args.port);
+            let config = match &args.config {
+                Some(path) => ServerConfig::from_file(path),
+                None => {
+                    let config = ServerConfig {
+                        listen: vec![format!("{}:{}", args.address, args.port)],
+                        heartbeat: HeartbeatConfig::new(
+                            args.ping_interval,
+                            args.idle_timeout,
+                            args.write_timeout,
+                        ),
+                        tls: match (args.tls_cert, args.tls_key) {
+                            (Some(cert), Some(key)) => Some(TlsFiles { cert, key }),
+                            _ => None,
+                        },
+                        ..ServerConfig::default()
+                    };
+                    config.validate().map(|_| config)
+                }
+            };
+            let config = match config {
+                Ok(config) => config,
+                Err(e) => {
+                    eprintln!("Error configuring the server: {}", e);
+                    return;
+                }
+            };
+
+            let tls = match &config.tls {
+                Some(files) => match tls::server_acceptor(&files.cert, &files.key) {
+                    Ok(acceptor) => Some(acceptor),
+                    Err(e) => {
+                        eprintln!("Error setting up TLS: {}", e);
+                        return;
+                    }
+                },
+                None => None,
+            };
+            if let Err(e) = start_service(config, args.config, tls) {
+                eprintln!("Error starting the server: {}", e);
+            }
         }
         Mode::Client => {
+            let tls = if args.tls || args.tls_ca.is_some() {
+                match tls::client_connector(args.tls_ca.as_deref()) {
+                    Ok(connector) => Some(connector),
+                    Err(e) => {
+                        eprintln!("Error setting up TLS: {}", e);
+                        return;
+                    }
+                }
+            } else {
+                None
+            };
             println!("Connecting to {}:{}", args.address, args.port);
-            panic!("Client mode is not implemented yet");
+            start_client(args.address, args.port, tls);
         }
         Mode::Local => {
-            LocalGame::new(&args.name, args.win_at).start();
+            let config = match &args.config {
+                Some(path) => match LocalConfig::from_file(path) {
+                    Ok(config) => config,
+                    Err(e) => {
+                        eprintln!("Error configuring the game: {}", e);
+                        return;
+                    }
+                },
+                None => LocalConfig::default(),
+            };
+            // The flags win over the configuration file
+            let mut theme = config.theme;
+            theme.ascii |= args.ascii;
+            theme.palette = args.palette.unwrap_or(theme.palette);
+            theme.suits = args.suits.unwrap_or(theme.suits);
+
+            let lang = args.lang.unwrap_or_else(Lang::from_locale);
+            let ui: Box<dyn Frontend> = if args.plain {
+                Box::new(PlainUI::new(lang, theme))
+            } else {
+                Box::new(UI::new(lang, theme))
+            };
+            match LocalGame::new(ui, &args.name, &args.bot, args.win_at) {
+                Ok(mut game) => game.start(),
+                Err(e) => eprintln!("{}", theme.text(&(lang.texts().game_error)(&e))),
+            }
         }
     }
 }
@@ -0,0 +1,230 @@
+use super::lobby::LobbyMessage;
+use log::{info, warn};
+use std::path::PathBuf;
+#[cfg(unix)]
+use std::{io, path::Path};
+use tokio::sync::mpsc::UnboundedSender;
+use tokio::sync::oneshot;
+use tokio_util::sync::CancellationToken;
+
+/// What the lobby answers to an admin command: the lines to show, or the
+/// reason it failed.
+pub type AdminReply = Result<String, String>;
+
+/// Requests an operator can send on the admin socket, one per line.
+pub enum AdminCommand {
+    Sessions,
+    Tables,
+    Kick(String),
+    Close(u8),
+    Announce(String),
+    Dump(u8),
+    Shutdown,
+}
+
+impl AdminCommand {
+    pub fn from_string(input: &str) -> Result<AdminCommand, String> {
+        let mut parts = input.split_whitespace();
+        let command = parts.next().unwrap_or_default().to_lowercase();
+        let argument = parts.collect::<Vec<&str>>().join(" ");
+        let table_id = || {
+            argument
+                .parse::<u8>()
+                .map_err(|_| format!("invalid table id: {}", argument))
+        };
+
+        match command.as_str() {
+            "sessions" => Ok(AdminCommand::Sessions),
+            "tables" => Ok(AdminCommand::Tables),
+            "kick" if !argument.is_empty() => Ok(AdminCommand::Kick(argument)),
+            "close" => Ok(AdminCommand::Close(table_id()?)),
+            "announce" if !argument.is_empty() => Ok(AdminCommand::Announce(argument)),
+            "dump" => Ok(AdminCommand::Dump(table_id()?)),
+            "shutdown" => Ok(AdminCommand::Shutdown),
+            _ => Err(format!("unknown command: {}", input.trim())),
+        }
+    }
+}
+
+/// Answers the operators connected to the Unix socket at `path`, until the
+/// server shuts down.
+#[cfg(unix)]
+pub fn listen(
+    path: PathBuf,
+    lobby_sender: UnboundedSender<LobbyMessage>,
+    shutdown: CancellationToken,
+) {
+    use std::fs;
+
+    let listener = match remove_stale_socket(&path).and_then(|_| bind_private(&path)) {
+        Ok(listener) => listener,
+        Err(e) => {
+            warn!("Cannot open admin socket {}: {}", path.display(), e);
+            return;
+        }
+    };
+    info!("Admin socket listening on {}", path.display());
+
+    tokio::spawn(async move {
+        loop {
+            let stream = tokio::select! {
+                _ = shutdown.cancelled() => break,
+                accepted = listener.accept() => match accepted {
+                    Ok((stream, _)) => stream,
+                    Err(e) => {
+                        warn!("Failed to accept admin connection: {}", e);
+                        continue;
+                    }
+                },
+            };
+            tokio::spawn(serve_operator(stream, lobby_sender.clone()));
+        }
+        let _ = fs::remove_file(&path);
+    });
+}
+
+/// Removes the socket a previous run left at `path`, which would make the
+/// bind fail; anything else there, or a socket still answering, is an error.
+#[cfg(unix)]
+fn remove_stale_socket(path: &Path) -> io::Result<()> {
+    use std::{fs, os::unix::fs::FileTypeExt, os::unix::net::UnixStream};
+
+    let metadata = match fs::symlink_metadata(path) {
+        Ok(metadata) => metadata,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e),
+    };
+    if !metadata.file_type().is_socket() {
+        return Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            "the path exists and is not a socket",
+        ));
+    }
+    match UnixStream::connect(path) {
+        Ok(_) => Err(io::Error::new(
+            io::ErrorKind::AddrInUse,
+            "another server is listening on it",
+        )),
+        Err(e) if e.kind() == io::ErrorKind::ConnectionRefused => fs::remove_file(path),
+        Err(e) => Err(e),
+    }
+}
+
+/// Binds the socket inside a directory only the server can enter, and
+/// links it to `path` once it is readable by the owner alone, so nobody
+/// can connect while the permissions are still the default ones.
+#[cfg(unix)]
+fn bind_private(path: &Path) -> io::Result<tokio::net::UnixListener> {
+    use std::{
+        fs::{self, DirBuilder},
+        os::unix::fs::{DirBuilderExt, PermissionsExt},
+    };
+
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let private = path.with_file_name(format!(".{}.{}", name, std::process::id()));
+    DirBuilder::new().mode(0o700).create(&private)?;
+    let socket = private.join("socket");
+    let bound = tokio::net::UnixListener::bind(&socket).and_then(|listener| {
+        fs::set_permissions(&socket, fs::Permissions::from_mode(0o600))?;
+        fs::hard_link(&socket, path)?;
+        Ok(listener)
+    });
+    fs::remove_dir_all(&private)?;
+    bound
+}
+
+#[cfg(not(unix))]
+pub fn listen(
+    path: PathBuf,
+    _lobby_sender: UnboundedSender<LobbyMessage>,
+    _shutdown: CancellationToken,
+) {
+    warn!("Admin socket {} is only supported on Unix", path.display());
+}
+
+#[cfg(unix)]
+async fn serve_operator(
+    stream: tokio::net::UnixStream,
+    lobby_sender: UnboundedSender<LobbyMessage>,
+) {
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+
+    let (reader, mut writer) = stream.into_split();
+    let mut lines = BufReader::new(reader).lines();
+    while let Ok(Some(line)) = lines.next_line().await {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let reply = match AdminCommand::from_string(&line) {
+            Ok(command) => {
+                info!("Admin command: {}", line.trim());
+                let (reply_sender, reply) = oneshot::channel();
+                if lobby_sender
+                    .send(LobbyMessage::Admin(command, reply_sender))
+                    .is_err()
+                {
+                    break;
+                }
+                match reply.await {
+                    Ok(Ok(lines)) => format!("{}OK\n", lines),
+                    Ok(Err(e)) => format!("ERROR: {}\n", e),
+                    Err(_) => "ERROR: no answer\n".to_string(),
+                }
+            }
+            Err(e) => format!("ERROR: {}\n", e),
+        };
+        if writer.write_all(reply.as_bytes()).await.is_err() {
+            break;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn commands_are_parsed() {
+        assert!(matches!(
+            AdminCommand::from_string("tables\n"),
+            Ok(AdminCommand::Tables)
+        ));
+        assert!(matches!(
+            AdminCommand::from_string("KICK Carlo Bo"),
+            Ok(AdminCommand::Kick(name)) if name == "Carlo Bo"
+        ));
+        assert!(matches!(
+            AdminCommand::from_string("dump 3"),
+            Ok(AdminCommand::Dump(3))
+        ));
+        assert!(AdminCommand::from_string("dump three").is_err());
+        assert!(AdminCommand::from_string("announce").is_err());
+        assert!(AdminCommand::from_string("reboot").is_err());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn only_stale_sockets_are_replaced() {
+        use std::{env, fs, os::unix::fs::PermissionsExt};
+
+        let dir = env::temp_dir().join(format!("cirulla-admin-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("admin.sock");
+
+        fs::write(&path, "not a socket").unwrap();
+        assert!(remove_stale_socket(&path).is_err());
+        assert!(path.exists());
+        fs::remove_file(&path).unwrap();
+
+        let listener = bind_private(&path).unwrap();
+        let mode = fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+        assert!(remove_stale_socket(&path).is_err());
+
+        drop(listener);
+        remove_stale_socket(&path).unwrap();
+        assert!(!path.exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,250 @@
+use cirulla_lib::{Card, HandResult};
+use log::warn;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
+
+/// Where the audit log is written and how it is rotated.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AuditConfig {
+    pub path: PathBuf,
+    /// Size in bytes after which the file is rotated
+    #[serde(default = "AuditConfig::default_max_size")]
+    pub max_size: u64,
+    /// How many rotated files are kept, as `path.1`, `path.2` and so on
+    #[serde(default = "AuditConfig::default_keep")]
+    pub keep: usize,
+}
+
+impl AuditConfig {
+    fn default_max_size() -> u64 {
+        10 * 1024 * 1024
+    }
+
+    fn default_keep() -> usize {
+        5
+    }
+}
+
+/// Something worth remembering about what happened on the server.
+#[derive(Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum AuditEvent {
+    Connected {
+        session: String,
+    },
+    Disconnected {
+        session: String,
+        name: Option<String>,
+    },
+    Hello {
+        session: String,
+        name: String,
+    },
+    TableCreated {
+        table: u8,
+        name: String,
+        owner: String,
+        player_max: u8,
+        win_at: u8,
+    },
+    TableJoined {
+        table: u8,
+        session: String,
+        name: String,
+    },
+    TableLeft {
+        table: u8,
+        session: String,
+    },
+    TableClosed {
+        table: u8,
+        finished: bool,
+    },
+    GameStarted {
+        table: u8,
+        players: Vec<String>,
+    },
+    Dealt {
+        table: u8,
+        hands: Vec<(String, Vec<Card>)>,
+        cards_on_table: Vec<Card>,
+        cards_in_deck: usize,
+    },
+    Play {
+        table: u8,
+        session: String,
+        player: String,
+        card: String,
+        hand: Vec<Card>,
+        cards_on_table: Vec<Card>,
+        captured: Vec<Card>,
+    },
+    PlayRejected {
+        table: u8,
+        session: String,
+        player: String,
+        card: String,
+        error: String,
+    },
+    HandResult {
+        table: u8,
+        result: HandResult,
+    },
+    GameEnded {
+        table: u8,
+        points: Vec<(String, u8)>,
+    },
+}
+
+#[derive(Serialize)]
+struct Record {
+    /// Milliseconds since the Unix epoch
+    ts: u128,
+    #[serde(flatten)]
+    event: AuditEvent,
+}
+
+/// The sending side of the audit log; a disabled log ignores every event.
+#[derive(Clone, Default)]
+pub struct AuditLog {
+    sender: Option<UnboundedSender<Record>>,
+}
+
+impl AuditLog {
+    /// Opens the log file and starts the thread writing into it.
+    pub fn open(config: &AuditConfig) -> io::Result<AuditLog> {
+        let writer = RotatingFile::open(config.clone())?;
+        let (sender, receiver) = unbounded_channel();
+        tokio::task::spawn_blocking(move || writer.write_records(receiver));
+        Ok(AuditLog {
+            sender: Some(sender),
+        })
+    }
+
+    pub fn record(&self, event: AuditEvent) {
+        if let Some(sender) = &self.sender {
+            let ts = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|elapsed| elapsed.as_millis())
+                .unwrap_or_default();
+            let _ = sender.send(Record { ts, event });
+        }
+    }
+}
+
+struct RotatingFile {
+    config: AuditConfig,
+    file: File,
+    size: u64,
+}
+
+impl RotatingFile {
+    fn open(config: AuditConfig) -> io::Result<RotatingFile> {
+        let file = Self::append_to(&config.path)?;
+        let size = file.metadata()?.len();
+        Ok(RotatingFile { config, file, size })
+    }
+
+    fn append_to(path: &Path) -> io::Result<File> {
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map_err(|e| io::Error::new(e.kind(), format!("cannot open {}: {}", path.display(), e)))
+    }
+
+    fn rotated(&self, index: usize) -> PathBuf {
+        let mut path = self.config.path.clone().into_os_string();
+        path.push(format!(".{}", index));
+        PathBuf::from(path)
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        if self.config.keep == 0 {
+            fs::remove_file(&self.config.path)?;
+        } else {
+            for index in (1..self.config.keep).rev() {
+                let from = self.rotated(index);
+                if from.exists() {
+                    fs::rename(from, self.rotated(index + 1))?;
+                }
+            }
+            fs::rename(&self.config.path, self.rotated(1))?;
+        }
+        self.file = Self::append_to(&self.config.path)?;
+        self.size = 0;
+        Ok(())
+    }
+
+    fn write(&mut self, line: &str) -> io::Result<()> {
+        if self.size > 0 && self.size + line.len() as u64 > self.config.max_size {
+            self.rotate()?;
+        }
+        // Every line is flushed: the log must survive a crash
+        self.file.write_all(line.as_bytes())?;
+        self.size += line.len() as u64;
+        Ok(())
+    }
+
+    fn write_records(mut self, mut records: UnboundedReceiver<Record>) {
+        while let Some(record) = records.blocking_recv() {
+            let line = match serde_json::to_string(&record) {
+                Ok(line) => line + "\n",
+                Err(e) => {
+                    warn!("Cannot serialize an audit record: {}", e);
+                    continue;
+                }
+            };
+            if let Err(e) = self.write(&line) {
+                warn!("Cannot write the audit log: {}", e);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    #[test]
+    fn records_are_json_lines_and_files_rotate() {
+        let dir = env::temp_dir().join(format!("cirulla-audit-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let config = AuditConfig {
+            path: dir.join("audit.jsonl"),
+            max_size: 200,
+            keep: 2,
+        };
+        let mut file = RotatingFile::open(config.clone()).unwrap();
+
+        for session in 0..10 {
+            let record = Record {
+                ts: 1,
+                event: AuditEvent::Hello {
+                    session: format!("127.0.0.1:{}", session),
+                    name: "Anna".to_string(),
+                },
+            };
+            let line = serde_json::to_string(&record).unwrap() + "\n";
+            file.write(&line).unwrap();
+        }
+
+        let current = fs::read_to_string(&config.path).unwrap();
+        let first = current.lines().next().unwrap();
+        let parsed: serde_json::Value = serde_json::from_str(first).unwrap();
+        assert_eq!(parsed["event"], "hello");
+        assert_eq!(parsed["name"], "Anna");
+        assert!(current.len() <= 200);
+        assert!(file.rotated(1).exists());
+        assert!(file.rotated(2).exists());
+        assert!(!file.rotated(3).exists());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,21 +1,63 @@
 use super::response::ServiceError;
+use crate::i18n::Lang;
+use std::str::SplitWhitespace;
 
 pub enum Command {
     Hello(String),
     Scream(String),
     Error(ServiceError),
     Quit,
+    Pong,
     TableNew((String, u8, u8)),
     TableList,
     TableJoin(u8),
     TableLeave,
     Status,
     Play(String),
+    Mute(String),
+    Unmute(String),
+    Admin(String),
+    Silence(String),
+    Unsilence(String),
+    Kick(String),
+    TableSay(String),
+    Who,
+    Whisper((String, String)),
+    Entropy(String),
+    Lang(Lang),
 }
 
 impl Command {
+    /// Short name of the command, as used in the metrics.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Command::Hello(_) => "hello",
+            Command::Scream(_) => "scream",
+            Command::Error(_) => "invalid",
+            Command::Quit => "quit",
+            Command::Pong => "pong",
+            Command::TableNew(_) => "table_new",
+            Command::TableList => "table_list",
+            Command::TableJoin(_) => "table_join",
+            Command::TableLeave => "table_leave",
+            Command::Status => "status",
+            Command::Play(_) => "play",
+            Command::Mute(_) => "mute",
+            Command::Unmute(_) => "unmute",
+            Command::Admin(_) => "admin",
+            Command::Silence(_) => "silence",
+            Command::Unsilence(_) => "unsilence",
+            Command::Kick(_) => "kick",
+            Command::TableSay(_) => "table_say",
+            Command::Who => "who",
+            Command::Whisper(_) => "whisper",
+            Command::Entropy(_) => "entropy",
+            Command::Lang(_) => "lang",
+        }
+    }
+
     pub fn from_string(input: &str) -> Command {
-        let mut parts = input.trim().split_whitespace();
+        let mut parts = input.split_whitespace();
 
         match parts.next() {
             Some(command) => match command.to_lowercase().as_str() {
@@ -27,26 +69,48 @@ impl Command {
                     let message = parts.collect::<Vec<&str>>().join(" ");
                     Command::Scream(message)
                 }
+                "mute" => Command::Mute(parts.collect::<Vec<&str>>().join(" ")),
+                "unmute" => Command::Unmute(parts.collect::<Vec<&str>>().join(" ")),
+                "admin" => Command::Admin(parts.collect::<Vec<&str>>().join(" ")),
+                "silence" => Command::Silence(parts.collect::<Vec<&str>>().join(" ")),
+                "unsilence" => Command::Unsilence(parts.collect::<Vec<&str>>().join(" ")),
+                "kick" => Command::Kick(parts.collect::<Vec<&str>>().join(" ")),
+                "whisper" => {
+                    let name = match parts.next() {
+                        Some(first) if first.starts_with('"') => read_quoted(first, &mut parts),
+                        Some(first) => first.to_string(),
+                        None => return Command::Error(ServiceError::InvalidCommand),
+                    };
+                    let message = parts.collect::<Vec<&str>>().join(" ");
+                    Command::Whisper((name, message))
+                }
+                "entropy" => {
+                    let entropy = parts.next().unwrap_or_default();
+                    if entropy.is_empty()
+                        || entropy.len() > 64
+                        || !entropy.chars().all(|c| c.is_ascii_hexdigit())
+                    {
+                        return Command::Error(ServiceError::InvalidEntropy);
+                    }
+                    Command::Entropy(entropy.to_string())
+                }
+                "lang" => match parts.next().and_then(Lang::from_code) {
+                    Some(lang) => Command::Lang(lang),
+                    None => Command::Error(ServiceError::InvalidLang),
+                },
                 "status" => Command::Status,
+                "who" => Command::Who,
                 "quit" => Command::Quit,
+                "pong" => Command::Pong,
                 "play" => Command::Play(parts.next().unwrap_or_default().to_string()),
                 "table" => match parts.next() {
                     Some(sub_command) => match sub_command.to_lowercase().as_str() {
                         "new" => {
-                            let mut name = parts.next().unwrap_or_default().to_string();
-                            if !name.starts_with('"') {
+                            let first = parts.next().unwrap_or_default();
+                            if !first.starts_with('"') {
                                 return Command::Error(ServiceError::TableNameNotQuoted);
                             }
-                            if !name.ends_with('"') {
-                                while let Some(part) = parts.next() {
-                                    name.push_str(" ");
-                                    name.push_str(part);
-                                    if part.ends_with('"') {
-                                        break;
-                                    }
-                                }
-                            }
-                            let table_name = name.trim_matches('"');
+                            let table_name = read_quoted(first, &mut parts);
 
                             let player_max =
                                 parts.next().unwrap_or_default().parse::<u8>().unwrap_or(2);
@@ -54,9 +118,10 @@ impl Command {
                             let win_at =
                                 parts.next().unwrap_or_default().parse::<u8>().unwrap_or(51);
 
-                            Command::TableNew((table_name.to_string(), player_max, win_at))
+                            Command::TableNew((table_name, player_max, win_at))
                         }
                         "list" => Command::TableList,
+                        "say" => Command::TableSay(parts.collect::<Vec<&str>>().join(" ")),
                         "leave" => Command::TableLeave,
                         "join" => Command::TableJoin(
                             parts.next().unwrap_or_default().parse::<u8>().unwrap_or(0),
@@ -71,3 +136,19 @@ impl Command {
         }
     }
 }
+
+/// Reads a double quoted string that may span several words, starting from
+/// the word that opens the quotes.
+fn read_quoted(first: &str, parts: &mut SplitWhitespace) -> String {
+    let mut quoted = first.to_string();
+    if quoted.len() == 1 || !quoted.ends_with('"') {
+        for part in parts.by_ref() {
+            quoted.push(' ');
+            quoted.push_str(part);
+            if part.ends_with('"') {
+                break;
+            }
+        }
+    }
+    quoted.trim_matches('"').to_string()
+}
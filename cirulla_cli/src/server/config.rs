@@ -0,0 +1,288 @@
+use super::audit::AuditConfig;
+use super::heartbeat::HeartbeatConfig;
+use serde::Deserialize;
+use std::{fmt::Display, fs, io, path::Path, path::PathBuf};
+
+/// Everything the server can be configured with, as read from a TOML file.
+///
+/// Only `motd`, `admin_password`, `presence` and `limits` are applied again when the
+/// server receives SIGHUP; listeners, TLS and heartbeat need a restart.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+    pub listen: Vec<String>,
+    pub motd: Option<String>,
+    /// Password that grants the moderation commands with ADMIN; without it
+    /// nobody can become admin
+    pub admin_password: Option<String>,
+    /// Whether every session is told when a user says HELLO or quits
+    pub presence: bool,
+    /// Unix socket where operators can manage the running server
+    pub admin_socket: Option<PathBuf>,
+    /// Where running games are saved on shutdown and restored from at start
+    pub state_file: Option<PathBuf>,
+    /// Address of the HTTP endpoint exposing the metrics on /metrics
+    pub metrics: Option<String>,
+    /// Rotating file recording sessions, tables and every play
+    pub audit: Option<AuditConfig>,
+    pub tls: Option<TlsFiles>,
+    pub heartbeat: HeartbeatConfig,
+    pub limits: Limits,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TlsFiles {
+    pub cert: PathBuf,
+    pub key: PathBuf,
+}
+
+/// Rules enforced by the lobby on every session.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Limits {
+    pub max_sessions: usize,
+    /// At most 255, the table ids being from 1 to 255
+    pub max_tables: usize,
+    pub max_tables_per_user: usize,
+    pub player_max: Range,
+    pub win_at: Range,
+    pub name_min_length: usize,
+    pub name_max_length: usize,
+    /// How many chat messages (SCREAM, TABLE SAY and WHISPER together) a
+    /// session can send every `chat_interval` seconds
+    pub chat_messages: usize,
+    pub chat_interval: u64,
+    pub chat_max_length: usize,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Range {
+    pub min: u8,
+    pub max: u8,
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Read(PathBuf, io::Error),
+    Parse(PathBuf, toml::de::Error),
+    Invalid(String),
+}
+
+impl Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ConfigError::Read(path, e) => write!(f, "cannot read {}: {}", path.display(), e),
+            ConfigError::Parse(path, e) => write!(f, "cannot parse {}: {}", path.display(), e),
+            ConfigError::Invalid(reason) => write!(f, "invalid configuration: {}", reason),
+        }
+    }
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        ServerConfig {
+            listen: vec!["localhost:15157".to_string()],
+            motd: None,
+            admin_password: None,
+            presence: false,
+            admin_socket: None,
+            state_file: None,
+            metrics: None,
+            audit: None,
+            tls: None,
+            heartbeat: HeartbeatConfig::default(),
+            limits: Limits::default(),
+        }
+    }
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Limits {
+            max_sessions: 1000,
+            max_tables: 250,
+            max_tables_per_user: 1,
+            player_max: Range { min: 2, max: 4 },
+            win_at: Range { min: 11, max: 201 },
+            name_min_length: 2,
+            name_max_length: 24,
+            chat_messages: 5,
+            chat_interval: 10,
+            chat_max_length: 300,
+        }
+    }
+}
+
+impl Range {
+    pub fn contains(&self, value: u8) -> bool {
+        self.min <= value && value <= self.max
+    }
+}
+
+impl Display for Range {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}-{}", self.min, self.max)
+    }
+}
+
+impl ServerConfig {
+    pub fn from_file(path: &Path) -> Result<ServerConfig, ConfigError> {
+        let content =
+            fs::read_to_string(path).map_err(|e| ConfigError::Read(path.to_path_buf(), e))?;
+        let config: ServerConfig =
+            toml::from_str(&content).map_err(|e| ConfigError::Parse(path.to_path_buf(), e))?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let invalid = |reason: &str| Err(ConfigError::Invalid(reason.to_string()));
+
+        if self.listen.is_empty() {
+            return invalid("at least one listen address is needed");
+        }
+        if self.heartbeat.ping_interval.is_zero() {
+            return invalid("heartbeat.ping_interval must be positive");
+        }
+        if self.heartbeat.idle_timeout <= self.heartbeat.ping_interval {
+            return invalid("heartbeat.idle_timeout must be longer than heartbeat.ping_interval");
+        }
+        if self.heartbeat.write_timeout.is_zero() {
+            return invalid("heartbeat.write_timeout must be positive");
+        }
+        self.limits.validate()
+    }
+}
+
+impl Limits {
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let invalid = |reason: &str| Err(ConfigError::Invalid(reason.to_string()));
+
+        if self.max_tables > u8::MAX as usize {
+            return invalid("limits.max_tables can be at most 255");
+        }
+        if self.player_max.min < 2 || self.player_max.max > 4 {
+            return invalid("limits.player_max must stay between 2 and 4");
+        }
+        if self.player_max.min > self.player_max.max {
+            return invalid("limits.player_max.min is greater than limits.player_max.max");
+        }
+        if self.win_at.min == 0 || self.win_at.min > self.win_at.max {
+            return invalid("limits.win_at must be a positive, non empty range");
+        }
+        if self.name_min_length < 2 {
+            return invalid("limits.name_min_length must be at least 2");
+        }
+        if self.name_min_length > self.name_max_length {
+            return invalid("limits.name_min_length is greater than limits.name_max_length");
+        }
+        if self.chat_interval == 0 {
+            return invalid("limits.chat_interval must be positive");
+        }
+        Ok(())
+    }
+
+    pub fn valid_name(&self, name: &str) -> bool {
+        let length = name.chars().count();
+        self.name_min_length <= length && length <= self.name_max_length
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn parse(content: &str) -> Result<ServerConfig, ConfigError> {
+        let config: ServerConfig =
+            toml::from_str(content).map_err(|e| ConfigError::Parse(PathBuf::new(), e))?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    #[test]
+    fn empty_file_gives_defaults() {
+        let config = parse("").unwrap();
+        assert_eq!(config.listen, vec!["localhost:15157"]);
+        assert_eq!(config.heartbeat.idle_timeout, Duration::from_secs(90));
+        assert_eq!(config.limits.max_tables_per_user, 1);
+    }
+
+    #[test]
+    fn full_file_is_read() {
+        let config = parse(
+            r#"
+            listen = ["0.0.0.0:15157", "[::]:15157"]
+            motd = "Benvenuti!"
+            presence = true
+
+            [audit]
+            path = "audit.jsonl"
+            max_size = 1048576
+
+            [tls]
+            cert = "cert.pem"
+            key = "key.pem"
+
+            [heartbeat]
+            ping_interval = 5
+            idle_timeout = 20
+
+            [limits]
+            max_sessions = 10
+            player_max = { min = 2, max = 2 }
+            win_at = { min = 21, max = 51 }
+            name_max_length = 12
+            chat_messages = 3
+            chat_max_length = 80
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(config.listen.len(), 2);
+        assert_eq!(config.motd.as_deref(), Some("Benvenuti!"));
+        assert!(config.presence);
+        let audit = config.audit.unwrap();
+        assert_eq!(audit.max_size, 1048576);
+        assert_eq!(audit.keep, 5);
+        assert_eq!(config.tls.unwrap().key, PathBuf::from("key.pem"));
+        assert_eq!(config.heartbeat.ping_interval, Duration::from_secs(5));
+        assert_eq!(config.heartbeat.write_timeout, Duration::from_secs(10));
+        assert_eq!(config.limits.max_sessions, 10);
+        assert!(!config.limits.player_max.contains(3));
+        assert!(config.limits.win_at.contains(51));
+        assert!(!config.limits.valid_name("A very long nickname"));
+        assert_eq!(config.limits.chat_interval, 10);
+        assert_eq!(config.limits.chat_max_length, 80);
+    }
+
+    #[test]
+    fn unknown_keys_are_refused() {
+        assert!(matches!(
+            parse("max_players = 3"),
+            Err(ConfigError::Parse(_, _))
+        ));
+    }
+
+    #[test]
+    fn impossible_limits_are_refused() {
+        assert!(matches!(
+            parse("[limits]\nplayer_max = { min = 2, max = 6 }"),
+            Err(ConfigError::Invalid(_))
+        ));
+        assert!(matches!(
+            parse("[limits]\nmax_tables = 256"),
+            Err(ConfigError::Invalid(_))
+        ));
+        assert!(matches!(
+            parse("[limits]\nname_min_length = 1"),
+            Err(ConfigError::Invalid(_))
+        ));
+        assert!(matches!(
+            parse("[heartbeat]\nping_interval = 30\nidle_timeout = 30"),
+            Err(ConfigError::Invalid(_))
+        ));
+    }
+}
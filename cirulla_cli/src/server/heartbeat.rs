@@ -0,0 +1,60 @@
+use serde::Deserialize;
+use std::time::Duration;
+
+/// Timing rules used to detect sessions that stopped responding.
+///
+/// The server sends a `PING` every `ping_interval`; clients are expected to
+/// answer with `PONG`. A session that stays silent for `idle_timeout` is
+/// considered dead and goes through the normal `Command::Quit` path.
+#[derive(Clone, Copy, Debug, Deserialize)]
+#[serde(from = "HeartbeatSeconds")]
+pub struct HeartbeatConfig {
+    pub ping_interval: Duration,
+    pub idle_timeout: Duration,
+    pub write_timeout: Duration,
+}
+
+/// How the heartbeat is written in the configuration file.
+#[derive(Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct HeartbeatSeconds {
+    ping_interval: u64,
+    idle_timeout: u64,
+    write_timeout: u64,
+}
+
+impl HeartbeatConfig {
+    pub fn new(ping_interval: u64, idle_timeout: u64, write_timeout: u64) -> HeartbeatConfig {
+        HeartbeatConfig {
+            ping_interval: Duration::from_secs(ping_interval),
+            idle_timeout: Duration::from_secs(idle_timeout),
+            write_timeout: Duration::from_secs(write_timeout),
+        }
+    }
+}
+
+impl Default for HeartbeatConfig {
+    fn default() -> Self {
+        HeartbeatSeconds::default().into()
+    }
+}
+
+impl Default for HeartbeatSeconds {
+    fn default() -> Self {
+        HeartbeatSeconds {
+            ping_interval: 30,
+            idle_timeout: 90,
+            write_timeout: 10,
+        }
+    }
+}
+
+impl From<HeartbeatSeconds> for HeartbeatConfig {
+    fn from(seconds: HeartbeatSeconds) -> Self {
+        HeartbeatConfig::new(
+            seconds.ping_interval,
+            seconds.idle_timeout,
+            seconds.write_timeout,
+        )
+    }
+}
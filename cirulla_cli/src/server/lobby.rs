@@ -0,0 +1,851 @@
+use super::admin::{AdminCommand, AdminReply};
+use super::audit::{AuditEvent, AuditLog};
+use super::command::Command;
+use super::config::{Limits, ServerConfig};
+use super::metrics::Metrics;
+use super::response::{Response, ServiceError, UserState};
+use super::session::Session;
+use super::snapshot::{self, TableSnapshot};
+use super::table::{Table, TableCommand, TableEvent, TableHandle};
+use log::{debug, info, warn};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender};
+use tokio::sync::oneshot;
+use tokio_util::sync::CancellationToken;
+
+/// Every how many chat violations of a session a warning is logged.
+const REPEATED_VIOLATIONS: usize = 3;
+
+/// Everything the lobby actor can be asked to handle.
+pub enum LobbyMessage {
+    Register(Session),
+    Command(String, Command),
+    Table(TableEvent),
+    Ping,
+    Reload(ServerConfig),
+    Admin(AdminCommand, oneshot::Sender<AdminReply>),
+    Shutdown,
+}
+
+/// Owns the connected sessions and routes their commands: lobby commands
+/// are handled here, game commands are forwarded to the table actors.
+pub struct Lobby {
+    sessions: HashMap<String, Session>,
+    tables: HashMap<u8, TableHandle>,
+    sender: UnboundedSender<LobbyMessage>,
+    motd: Option<String>,
+    admin_password: Option<String>,
+    presence: bool,
+    limits: Limits,
+    state_file: Option<PathBuf>,
+    shutdown: CancellationToken,
+    metrics: Arc<Metrics>,
+    audit: AuditLog,
+}
+
+impl Lobby {
+    pub fn new(
+        sender: UnboundedSender<LobbyMessage>,
+        config: &ServerConfig,
+        shutdown: CancellationToken,
+        metrics: Arc<Metrics>,
+        audit: AuditLog,
+    ) -> Lobby {
+        Lobby {
+            sessions: HashMap::new(),
+            tables: HashMap::new(),
+            sender,
+            motd: config.motd.clone(),
+            admin_password: config.admin_password.clone(),
+            presence: config.presence,
+            limits: config.limits.clone(),
+            state_file: config.state_file.clone(),
+            shutdown,
+            metrics,
+            audit,
+        }
+    }
+
+    pub async fn run(mut self, mut messages: UnboundedReceiver<LobbyMessage>) {
+        while let Some(message) = messages.recv().await {
+            match message {
+                LobbyMessage::Register(session) => self.register_session(session),
+                LobbyMessage::Command(session_id, command) => self.execute(&session_id, command),
+                LobbyMessage::Table(event) => self.table_event(event),
+                LobbyMessage::Ping => self.ping(),
+                LobbyMessage::Reload(config) => self.reload(config),
+                LobbyMessage::Admin(command, reply) => self.admin_command(command, reply),
+                LobbyMessage::Shutdown => self.shutdown().await,
+            }
+            if self.shutdown.is_cancelled() {
+                break;
+            }
+            self.update_metrics();
+        }
+    }
+
+    fn update_metrics(&self) {
+        let playing = self.tables.values().filter(|t| t.info.started).count();
+        self.metrics.set_sessions(self.sessions.len());
+        self.metrics
+            .set_tables(self.tables.len() - playing, playing);
+    }
+
+    /// Reopens the tables saved by the last shutdown.
+    pub fn restore(&mut self, snapshots: Vec<TableSnapshot>) {
+        for snapshot in snapshots {
+            let mut table = Table::restore(snapshot, self.metrics.clone(), self.audit.clone())
+                .spawn(self.sender.clone());
+            info!("Restored table {}", table.info.id);
+            table.announced = true;
+            self.tables.insert(table.info.id, table);
+        }
+    }
+
+    fn reload(&mut self, config: ServerConfig) {
+        info!("Reloading message of the day, admin password, presence and limits");
+        self.motd = config.motd;
+        self.admin_password = config.admin_password;
+        self.presence = config.presence;
+        self.limits = config.limits;
+    }
+
+    fn register_session(&mut self, mut session: Session) {
+        let id = session.id.clone();
+        if self.sessions.len() >= self.limits.max_sessions {
+            warn!("Refusing session {}: too many sessions", id);
+            session.send_response(Response::Error(ServiceError::ServerFull));
+            session.disconnect();
+            return;
+        }
+
+        debug!("Registering session {}", id);
+        self.audit.record(AuditEvent::Connected {
+            session: id.clone(),
+        });
+        session.read_commands();
+        if let Some(motd) = &self.motd {
+            session.send_response(Response::Motd(motd.clone()));
+        }
+        self.sessions.insert(id, session);
+    }
+
+    fn unregister_session(&mut self, id: &str) {
+        debug!("Unregistering session {}", id);
+        if let Some(session) = self.sessions.remove(id) {
+            self.audit.record(AuditEvent::Disconnected {
+                session: id.to_string(),
+                name: session.name.clone(),
+            });
+            if let Some(table_id) = session.table {
+                self.forward(table_id, TableCommand::Quit(id.to_string()));
+            }
+            session.disconnect();
+            if let Some(name) = session.name {
+                self.announce(Response::UserLeft(name));
+            }
+        }
+    }
+
+    fn execute(&mut self, session_id: &str, command: Command) {
+        if !self.sessions.contains_key(session_id) {
+            debug!("Ignoring command from unknown session {}", session_id);
+            return;
+        }
+        self.metrics.command(command.name());
+
+        match command {
+            Command::Error(message) => {
+                self.error(session_id, message);
+            }
+            Command::Hello(name) => {
+                self.hello(session_id, name);
+            }
+            Command::Scream(message) => {
+                self.scream(session_id, message);
+            }
+            Command::Quit => {
+                self.unregister_session(session_id);
+            }
+            Command::Pong => {
+                debug!("Session {} is alive", session_id);
+            }
+            Command::TableNew((name, player_max, win_at)) => {
+                self.table_new(session_id, name, player_max, win_at);
+            }
+            Command::TableList => {
+                self.table_list(session_id);
+            }
+            Command::TableJoin(table_id) => {
+                self.table_join(session_id, table_id);
+            }
+            Command::TableLeave => {
+                self.table_leave(session_id);
+            }
+            Command::Status => {
+                self.status(session_id);
+            }
+            Command::Who => {
+                self.who(session_id);
+            }
+            Command::Play(card) => {
+                self.play(session_id, card);
+            }
+            Command::Mute(name) => {
+                self.mute(session_id, name, true);
+            }
+            Command::Unmute(name) => {
+                self.mute(session_id, name, false);
+            }
+            Command::Admin(password) => {
+                self.admin(session_id, password);
+            }
+            Command::Silence(name) => {
+                self.silence(session_id, name, true);
+            }
+            Command::Unsilence(name) => {
+                self.silence(session_id, name, false);
+            }
+            Command::Kick(name) => {
+                self.kick(session_id, name);
+            }
+            Command::TableSay(message) => {
+                self.table_say(session_id, message);
+            }
+            Command::Whisper((name, message)) => {
+                self.whisper(session_id, name, message);
+            }
+            Command::Entropy(entropy) => {
+                self.entropy(session_id, entropy);
+            }
+            Command::Lang(lang) => {
+                self.sessions[session_id].send_response(Response::Lang(lang));
+            }
+        }
+    }
+
+    fn table_event(&mut self, event: TableEvent) {
+        match event {
+            TableEvent::Updated(info) => {
+                if let Some(table) = self.tables.get_mut(&info.id) {
+                    table.info = info.clone();
+                    if !table.announced {
+                        table.announced = true;
+                        self.broadcast(Response::TableCreated(info));
+                    }
+                }
+            }
+            TableEvent::Left(table_id, session_id) => {
+                if let Some(session) = self.sessions.get_mut(&session_id) {
+                    if session.table == Some(table_id) {
+                        session.table = None;
+                    }
+                }
+            }
+            TableEvent::Closed(table_id) => {
+                self.table_closed(table_id);
+            }
+        }
+    }
+
+    fn table_closed(&mut self, table_id: u8) {
+        for session in self.sessions.values_mut() {
+            if session.table == Some(table_id) {
+                session.table = None;
+            }
+        }
+        if let Some(table) = self.tables.remove(&table_id) {
+            if table.announced {
+                self.broadcast(Response::TableRemoved(table_id));
+            }
+        }
+    }
+
+    fn forward(&mut self, table_id: u8, command: TableCommand) {
+        let delivered = match self.tables.get(&table_id) {
+            Some(table) => table.send(command),
+            None => false,
+        };
+        if !delivered {
+            warn!("Table {} is not running anymore", table_id);
+            self.table_closed(table_id);
+        }
+    }
+
+    fn play(&mut self, session_id: &str, card: String) {
+        match self.sessions[session_id].table {
+            Some(table_id) => {
+                self.forward(
+                    table_id,
+                    TableCommand::Play(session_id.to_string(), card, Instant::now()),
+                );
+            }
+            None => {
+                self.error(session_id, ServiceError::TableNotFound);
+            }
+        }
+    }
+
+    fn entropy(&mut self, session_id: &str, entropy: String) {
+        match self.sessions[session_id].table {
+            Some(table_id) => {
+                self.forward(
+                    table_id,
+                    TableCommand::Entropy(session_id.to_string(), entropy),
+                );
+            }
+            None => {
+                self.error(session_id, ServiceError::TableNotFound);
+            }
+        }
+    }
+
+    fn status(&mut self, session_id: &str) {
+        let session = &self.sessions[session_id];
+
+        let name = match &session.name {
+            Some(name) => name.clone(),
+            None => "".to_string(),
+        };
+        let table = session.table.unwrap_or(0);
+
+        session.send_response(Response::Status((name, table)));
+    }
+
+    fn user_state(&self, session: &Session) -> UserState {
+        match session.table.and_then(|id| self.tables.get(&id)) {
+            Some(table) if table.info.started => UserState::Playing(table.info.id),
+            Some(table) => UserState::Waiting(table.info.id),
+            None => UserState::Lobby,
+        }
+    }
+
+    fn who(&mut self, session_id: &str) {
+        let mut users: Vec<(String, UserState)> = self
+            .sessions
+            .values()
+            .filter_map(|session| Some((session.name.clone()?, self.user_state(session))))
+            .collect();
+        users.sort_unstable_by(|a, b| a.0.cmp(&b.0));
+        self.sessions[session_id].send_response(Response::Who(users));
+    }
+
+    fn table_list(&mut self, session_id: &str) {
+        let tables = self.tables.values().map(|t| t.info.clone()).collect();
+        self.sessions[session_id].send_response(Response::TableList(tables));
+    }
+
+    fn table_join(&mut self, session_id: &str, table_id: u8) {
+        if self.sessions[session_id].table.is_some() {
+            self.error(session_id, ServiceError::TableAlreadyJoined);
+            return;
+        }
+
+        if !self.tables.contains_key(&table_id) {
+            self.error(session_id, ServiceError::TableNotFound);
+            return;
+        }
+
+        let session = self
+            .sessions
+            .get_mut(session_id)
+            .expect("Invalid session ID");
+
+        let player_name = match session.name {
+            Some(ref name) => name.clone(),
+            None => {
+                self.error(session_id, ServiceError::NotHello);
+                return;
+            }
+        };
+
+        session.table = Some(table_id);
+        let handle = session.handle();
+        self.forward(table_id, TableCommand::Join(handle, player_name));
+    }
+
+    fn table_new(&mut self, session_id: &str, name: String, player_max: u8, win_at: u8) {
+        if self.sessions[session_id].table.is_some() {
+            self.error(session_id, ServiceError::TableAlreadyJoined);
+            return;
+        }
+
+        if !self.limits.player_max.contains(player_max) {
+            self.error(
+                session_id,
+                ServiceError::InvalidPlayerMax(self.limits.player_max),
+            );
+            return;
+        }
+
+        if !self.limits.win_at.contains(win_at) {
+            self.error(session_id, ServiceError::InvalidWinAt(self.limits.win_at));
+            return;
+        }
+
+        let player_name = match self.sessions[session_id].name {
+            Some(ref name) => name.clone(),
+            None => {
+                self.error(session_id, ServiceError::NotHello);
+                return;
+            }
+        };
+
+        let owned_tables = self
+            .tables
+            .values()
+            .filter(|t| t.owner == player_name)
+            .count();
+        // The lowest id not taken, as 0 stands for no table
+        let free_id = (1..=u8::MAX).find(|id| !self.tables.contains_key(id));
+        let table_id = match free_id {
+            Some(table_id)
+                if self.tables.len() < self.limits.max_tables
+                    && owned_tables < self.limits.max_tables_per_user =>
+            {
+                table_id
+            }
+            _ => {
+                self.error(session_id, ServiceError::TooManyTables);
+                return;
+            }
+        };
+
+        let session = self
+            .sessions
+            .get_mut(session_id)
+            .expect("Invalid session ID");
+        let table = Table::new(
+            table_id,
+            name.clone(),
+            player_name.clone(),
+            player_max,
+            win_at,
+            self.metrics.clone(),
+            self.audit.clone(),
+        )
+        .spawn(self.sender.clone());
+        self.audit.record(AuditEvent::TableCreated {
+            table: table_id,
+            name,
+            owner: player_name.clone(),
+            player_max,
+            win_at,
+        });
+        self.tables.insert(table_id, table);
+
+        session.table = Some(table_id);
+        let handle = session.handle();
+        self.forward(table_id, TableCommand::Join(handle, player_name));
+    }
+
+    fn table_leave(&mut self, session_id: &str) {
+        if let Some(table_id) = self.sessions[session_id].table {
+            self.forward(table_id, TableCommand::Leave(session_id.to_string()));
+        }
+    }
+
+    fn error(&mut self, session_id: &str, code: ServiceError) {
+        self.sessions[session_id].send_response(Response::Error(code));
+    }
+
+    fn hello(&mut self, session_id: &str, name: String) {
+        if !self.limits.valid_name(&name) {
+            self.error(
+                session_id,
+                ServiceError::InvalidName(self.limits.name_min_length, self.limits.name_max_length),
+            );
+            return;
+        }
+
+        let existent = self
+            .sessions
+            .values()
+            .filter(|s| s.name == Some(name.clone()))
+            .count();
+
+        let session = self
+            .sessions
+            .get_mut(session_id)
+            .expect("Invalid session ID");
+
+        if existent > 0 {
+            session.send_response(Response::Error(ServiceError::NameInUse));
+            return;
+        }
+
+        let previous = session.name.replace(name.clone());
+        self.audit.record(AuditEvent::Hello {
+            session: session_id.to_string(),
+            name: name.clone(),
+        });
+        session.send_response(Response::Hi(name.clone()));
+        if let Some(previous) = previous {
+            self.announce(Response::UserLeft(previous));
+        }
+        self.announce(Response::UserJoined(name));
+    }
+
+    /// Checks that a session is allowed to send a chat message right now,
+    /// giving back the name it speaks with.
+    fn chat_sender(&mut self, session_id: &str, message: &str) -> Option<String> {
+        let chat_interval = Duration::from_secs(self.limits.chat_interval);
+        let session = self
+            .sessions
+            .get_mut(session_id)
+            .expect("Invalid session ID");
+        let name = match &session.name {
+            Some(name) => name.clone(),
+            None => {
+                self.error(session_id, ServiceError::NotHello);
+                return None;
+            }
+        };
+
+        let violation = if session.silenced {
+            Some(ServiceError::Silenced)
+        } else if message.chars().count() > self.limits.chat_max_length {
+            Some(ServiceError::ChatTooLong(self.limits.chat_max_length))
+        } else if !session.try_chat(self.limits.chat_messages, chat_interval) {
+            Some(ServiceError::ChatRateLimited)
+        } else {
+            None
+        };
+        if let Some(violation) = violation {
+            session.chat_violations += 1;
+            if session.chat_violations.is_multiple_of(REPEATED_VIOLATIONS) {
+                warn!(
+                    "Session {} ({}) broke the chat rules {} times",
+                    session_id, name, session.chat_violations
+                );
+            }
+            self.error(session_id, violation);
+            return None;
+        }
+
+        Some(name)
+    }
+
+    fn scream(&mut self, session_id: &str, message: String) {
+        let name = match self.chat_sender(session_id, &message) {
+            Some(name) => name,
+            None => return,
+        };
+
+        let message = Response::Scream((name.clone(), message));
+        for session in self.sessions.values() {
+            if !session.muted.contains(&name) {
+                session.send_response(message.clone());
+            }
+        }
+    }
+
+    fn table_say(&mut self, session_id: &str, message: String) {
+        let table_id = match self.sessions[session_id].table {
+            Some(table_id) => table_id,
+            None => {
+                self.error(session_id, ServiceError::TableNotFound);
+                return;
+            }
+        };
+        let name = match self.chat_sender(session_id, &message) {
+            Some(name) => name,
+            None => return,
+        };
+
+        let message = Response::TableSay((name.clone(), message));
+        for session in self.sessions.values() {
+            if session.table == Some(table_id) && !session.muted.contains(&name) {
+                session.send_response(message.clone());
+            }
+        }
+    }
+
+    fn whisper(&mut self, session_id: &str, recipient: String, message: String) {
+        let recipient_id = match self.session_by_name(&recipient) {
+            Some(session) => session.id.clone(),
+            None => {
+                self.error(session_id, ServiceError::UserNotFound);
+                return;
+            }
+        };
+        let name = match self.chat_sender(session_id, &message) {
+            Some(name) => name,
+            None => return,
+        };
+
+        let recipient = &self.sessions[&recipient_id];
+        if !recipient.muted.contains(&name) {
+            recipient.send_response(Response::Whisper((name, message)));
+        }
+    }
+
+    fn mute(&mut self, session_id: &str, name: String, muted: bool) {
+        if self.session_by_name(&name).is_none() {
+            self.error(session_id, ServiceError::UserNotFound);
+            return;
+        }
+
+        let session = self
+            .sessions
+            .get_mut(session_id)
+            .expect("Invalid session ID");
+        if muted {
+            session.muted.insert(name.clone());
+            session.send_response(Response::Muted(name));
+        } else {
+            session.muted.remove(&name);
+            session.send_response(Response::Unmuted(name));
+        }
+    }
+
+    fn admin(&mut self, session_id: &str, password: String) {
+        let session = self
+            .sessions
+            .get_mut(session_id)
+            .expect("Invalid session ID");
+        match &self.admin_password {
+            Some(admin_password) if *admin_password == password => {
+                info!("Session {} is now admin", session_id);
+                session.admin = true;
+                session.send_response(Response::Admin);
+            }
+            _ => {
+                warn!("Session {} failed to become admin", session_id);
+                session.send_response(Response::Error(ServiceError::WrongPassword));
+            }
+        }
+    }
+
+    fn silence(&mut self, session_id: &str, name: String, silenced: bool) {
+        if !self.sessions[session_id].admin {
+            self.error(session_id, ServiceError::NotAdmin);
+            return;
+        }
+
+        let target_id = match self.session_by_name(&name) {
+            Some(target) => target.id.clone(),
+            None => {
+                self.error(session_id, ServiceError::UserNotFound);
+                return;
+            }
+        };
+
+        info!(
+            "Session {} set silenced={} on {}",
+            session_id, silenced, name
+        );
+        let response = if silenced {
+            Response::Silenced(name)
+        } else {
+            Response::Unsilenced(name)
+        };
+        let target = self
+            .sessions
+            .get_mut(&target_id)
+            .expect("Invalid session ID");
+        target.silenced = silenced;
+        target.send_response(response.clone());
+        if target_id != session_id {
+            self.sessions[session_id].send_response(response);
+        }
+    }
+
+    fn kick(&mut self, session_id: &str, name: String) {
+        if !self.sessions[session_id].admin {
+            self.error(session_id, ServiceError::NotAdmin);
+            return;
+        }
+
+        let target_id = match self.session_by_name(&name) {
+            Some(target) => target.id.clone(),
+            None => {
+                self.error(session_id, ServiceError::UserNotFound);
+                return;
+            }
+        };
+
+        info!("Session {} kicked {}", session_id, name);
+        if target_id != session_id {
+            self.sessions[session_id].send_response(Response::Kicked(name.clone()));
+        }
+        self.kick_session(&target_id, name);
+    }
+
+    fn kick_session(&mut self, session_id: &str, name: String) {
+        self.sessions[session_id].send_response(Response::Kicked(name));
+        self.unregister_session(session_id);
+    }
+
+    fn admin_command(&mut self, command: AdminCommand, reply: oneshot::Sender<AdminReply>) {
+        let result = match command {
+            AdminCommand::Sessions => {
+                let mut sessions: Vec<&Session> = self.sessions.values().collect();
+                sessions.sort_unstable_by(|a, b| a.id.cmp(&b.id));
+                let mut lines = String::new();
+                for session in sessions {
+                    lines.push_str(&format!(
+                        "{} {}{}{} {}\n",
+                        session.id,
+                        self.user_state(session),
+                        if session.admin { " ADMIN" } else { "" },
+                        if session.silenced { " SILENCED" } else { "" },
+                        session.name.as_deref().unwrap_or("-"),
+                    ));
+                }
+                Ok(lines)
+            }
+            AdminCommand::Tables => {
+                let mut tables: Vec<&TableHandle> = self.tables.values().collect();
+                tables.sort_unstable_by_key(|t| t.info.id);
+                let mut lines = String::new();
+                for table in tables {
+                    lines.push_str(&format!(
+                        "{} \"{}\" {}/{} {} {} {}\n",
+                        table.info.id,
+                        table.info.name,
+                        table.info.player_count,
+                        table.info.player_max,
+                        table.info.win_at,
+                        if table.info.started {
+                            "PLAYING"
+                        } else {
+                            "WAITING"
+                        },
+                        table.owner,
+                    ));
+                }
+                Ok(lines)
+            }
+            AdminCommand::Kick(target) => {
+                let target_id = self
+                    .sessions
+                    .get(&target)
+                    .or_else(|| self.session_by_name(&target))
+                    .map(|session| session.id.clone());
+                match target_id {
+                    Some(target_id) => {
+                        let name = self.sessions[&target_id].name.clone().unwrap_or(target);
+                        info!("Admin kicked session {}", target_id);
+                        self.kick_session(&target_id, name);
+                        Ok(String::new())
+                    }
+                    None => Err("user not found".to_string()),
+                }
+            }
+            AdminCommand::Close(table_id) => {
+                if self.tables.contains_key(&table_id) {
+                    self.forward(table_id, TableCommand::Close);
+                    Ok(String::new())
+                } else {
+                    Err("table not found".to_string())
+                }
+            }
+            AdminCommand::Announce(message) => {
+                self.broadcast(Response::Announce(message));
+                Ok(String::new())
+            }
+            AdminCommand::Dump(table_id) => match self.tables.get(&table_id) {
+                // The table actor answers the operator by itself
+                Some(table) => {
+                    if !table.send(TableCommand::Dump(reply)) {
+                        warn!("Table {} is not running anymore", table_id);
+                    }
+                    return;
+                }
+                None => Err("table not found".to_string()),
+            },
+            AdminCommand::Shutdown => {
+                let _ = self.sender.send(LobbyMessage::Shutdown);
+                Ok(String::new())
+            }
+        };
+        let _ = reply.send(result);
+    }
+
+    /// Tells every session the server is going away, saves the running
+    /// games, disconnects everybody and lets the server stop.
+    async fn shutdown(&mut self) {
+        if self.shutdown.is_cancelled() {
+            return;
+        }
+        info!("Shutting down");
+        self.broadcast(Response::Shutdown);
+        if let Some(path) = self.state_file.clone() {
+            self.save_tables(&path).await;
+        }
+        let session_ids: Vec<String> = self.sessions.keys().cloned().collect();
+        for session_id in session_ids {
+            self.unregister_session(&session_id);
+        }
+        self.shutdown.cancel();
+    }
+
+    async fn save_tables(&mut self, path: &Path) {
+        let mut replies = Vec::new();
+        for table in self.tables.values().filter(|t| t.info.started) {
+            let (reply_sender, reply) = oneshot::channel();
+            if table.send(TableCommand::Snapshot(reply_sender)) {
+                replies.push(reply);
+            }
+        }
+
+        let mut snapshots = Vec::new();
+        for reply in replies {
+            match reply.await {
+                Ok(snapshot) => snapshots.push(snapshot),
+                Err(_) => warn!("A table stopped before being saved"),
+            }
+        }
+        if snapshots.is_empty() {
+            return;
+        }
+
+        // Saved tables are not running anymore, so their sessions have none
+        // to quit when they are unregistered
+        for snapshot in snapshots.iter() {
+            self.tables.remove(&snapshot.id);
+            for session in self.sessions.values_mut() {
+                if session.table == Some(snapshot.id) {
+                    session.table = None;
+                }
+            }
+        }
+        match snapshot::save(path, &snapshots) {
+            Ok(_) => info!("Saved {} tables to {}", snapshots.len(), path.display()),
+            Err(e) => warn!("Cannot save tables to {}: {}", path.display(), e),
+        }
+    }
+
+    fn session_by_name(&self, name: &str) -> Option<&Session> {
+        self.sessions
+            .values()
+            .find(|s| s.name.as_deref() == Some(name))
+    }
+
+    fn ping(&mut self) {
+        for session in self.sessions.values() {
+            session.send_response(Response::Ping);
+        }
+    }
+
+    /// Tells the named sessions about users coming and going, when the
+    /// server is configured to do so.
+    fn announce(&mut self, message: Response) {
+        if !self.presence {
+            return;
+        }
+        for session in self.sessions.values().filter(|s| s.name.is_some()) {
+            session.send_response(message.clone());
+        }
+    }
+
+    fn broadcast(&mut self, message: Response) {
+        for session in self.sessions.values() {
+            session.send_response(message.clone());
+        }
+    }
+}
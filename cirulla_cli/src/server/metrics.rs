@@ -0,0 +1,237 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Upper bounds, in seconds, of the move latency histogram buckets.
+const LATENCY_BUCKETS: [f64; 8] = [0.0005, 0.001, 0.005, 0.01, 0.05, 0.1, 0.5, 1.0];
+
+/// Counters and gauges describing the server, rendered in the Prometheus
+/// text format.
+#[derive(Default)]
+pub struct Metrics {
+    sessions: AtomicU64,
+    tables_waiting: AtomicU64,
+    tables_playing: AtomicU64,
+    games_started: AtomicU64,
+    games_finished: AtomicU64,
+    commands: Mutex<BTreeMap<&'static str, u64>>,
+    errors: Mutex<BTreeMap<&'static str, u64>>,
+    move_latency: Histogram,
+}
+
+#[derive(Default)]
+struct Histogram {
+    buckets: [AtomicU64; LATENCY_BUCKETS.len()],
+    count: AtomicU64,
+    sum_micros: AtomicU64,
+}
+
+impl Metrics {
+    pub fn set_sessions(&self, sessions: usize) {
+        self.sessions.store(sessions as u64, Ordering::Relaxed);
+    }
+
+    pub fn set_tables(&self, waiting: usize, playing: usize) {
+        self.tables_waiting.store(waiting as u64, Ordering::Relaxed);
+        self.tables_playing.store(playing as u64, Ordering::Relaxed);
+    }
+
+    pub fn game_started(&self) {
+        self.games_started.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn game_finished(&self) {
+        self.games_finished.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn command(&self, name: &'static str) {
+        *self.commands.lock().unwrap().entry(name).or_default() += 1;
+    }
+
+    pub fn error(&self, name: &'static str) {
+        *self.errors.lock().unwrap().entry(name).or_default() += 1;
+    }
+
+    pub fn move_played(&self, latency: Duration) {
+        let seconds = latency.as_secs_f64();
+        for (bucket, bound) in self.move_latency.buckets.iter().zip(LATENCY_BUCKETS) {
+            if seconds <= bound {
+                bucket.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+        self.move_latency.count.fetch_add(1, Ordering::Relaxed);
+        self.move_latency
+            .sum_micros
+            .fetch_add(latency.as_micros() as u64, Ordering::Relaxed);
+    }
+
+    #[cfg_attr(not(feature = "metrics"), allow(dead_code))]
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        let load = |value: &AtomicU64| value.load(Ordering::Relaxed);
+
+        let _ = writeln!(out, "# HELP cirulla_sessions Connected sessions.");
+        let _ = writeln!(out, "# TYPE cirulla_sessions gauge");
+        let _ = writeln!(out, "cirulla_sessions {}", load(&self.sessions));
+
+        let _ = writeln!(out, "# HELP cirulla_tables Open tables by state.");
+        let _ = writeln!(out, "# TYPE cirulla_tables gauge");
+        let _ = writeln!(
+            out,
+            "cirulla_tables{{state=\"waiting\"}} {}",
+            load(&self.tables_waiting)
+        );
+        let _ = writeln!(
+            out,
+            "cirulla_tables{{state=\"playing\"}} {}",
+            load(&self.tables_playing)
+        );
+
+        let _ = writeln!(out, "# HELP cirulla_games_started_total Games started.");
+        let _ = writeln!(out, "# TYPE cirulla_games_started_total counter");
+        let _ = writeln!(
+            out,
+            "cirulla_games_started_total {}",
+            load(&self.games_started)
+        );
+
+        let _ = writeln!(
+            out,
+            "# HELP cirulla_games_finished_total Games played to the end."
+        );
+        let _ = writeln!(out, "# TYPE cirulla_games_finished_total counter");
+        let _ = writeln!(
+            out,
+            "cirulla_games_finished_total {}",
+            load(&self.games_finished)
+        );
+
+        let _ = writeln!(
+            out,
+            "# HELP cirulla_commands_total Commands received by type."
+        );
+        let _ = writeln!(out, "# TYPE cirulla_commands_total counter");
+        for (command, count) in self.commands.lock().unwrap().iter() {
+            let _ = writeln!(
+                out,
+                "cirulla_commands_total{{command=\"{}\"}} {}",
+                command, count
+            );
+        }
+
+        let _ = writeln!(
+            out,
+            "# HELP cirulla_errors_total Errors sent to clients by type."
+        );
+        let _ = writeln!(out, "# TYPE cirulla_errors_total counter");
+        for (error, count) in self.errors.lock().unwrap().iter() {
+            let _ = writeln!(out, "cirulla_errors_total{{error=\"{}\"}} {}", error, count);
+        }
+
+        let _ = writeln!(
+            out,
+            "# HELP cirulla_move_latency_seconds Time from receiving a PLAY to answering it."
+        );
+        let _ = writeln!(out, "# TYPE cirulla_move_latency_seconds histogram");
+        for (bucket, bound) in self.move_latency.buckets.iter().zip(LATENCY_BUCKETS) {
+            let _ = writeln!(
+                out,
+                "cirulla_move_latency_seconds_bucket{{le=\"{}\"}} {}",
+                bound,
+                load(bucket)
+            );
+        }
+        let count = load(&self.move_latency.count);
+        let _ = writeln!(
+            out,
+            "cirulla_move_latency_seconds_bucket{{le=\"+Inf\"}} {}",
+            count
+        );
+        let _ = writeln!(
+            out,
+            "cirulla_move_latency_seconds_sum {}",
+            load(&self.move_latency.sum_micros) as f64 / 1_000_000.0
+        );
+        let _ = writeln!(out, "cirulla_move_latency_seconds_count {}", count);
+
+        out
+    }
+}
+
+/// Answers `GET /metrics` on `listener` until the server shuts down.
+#[cfg(feature = "metrics")]
+pub async fn serve(
+    listener: tokio::net::TcpListener,
+    metrics: std::sync::Arc<Metrics>,
+    shutdown: tokio_util::sync::CancellationToken,
+) {
+    use log::{debug, warn};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::time::timeout;
+
+    loop {
+        let mut stream = tokio::select! {
+            _ = shutdown.cancelled() => break,
+            accepted = listener.accept() => match accepted {
+                Ok((stream, _)) => stream,
+                Err(e) => {
+                    warn!("Failed to accept metrics connection: {}", e);
+                    continue;
+                }
+            },
+        };
+        let metrics = metrics.clone();
+        tokio::spawn(async move {
+            // Only the request line matters, and it fits the first read
+            let mut request = [0u8; 1024];
+            let read = match timeout(Duration::from_secs(5), stream.read(&mut request)).await {
+                Ok(Ok(read)) => read,
+                _ => return,
+            };
+            let request = String::from_utf8_lossy(&request[..read]);
+            let response = if request.starts_with("GET /metrics ") {
+                let body = metrics.render();
+                format!(
+                    "HTTP/1.1 200 OK\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    body.len(),
+                    body
+                )
+            } else {
+                "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+                    .to_string()
+            };
+            if let Err(e) = stream.write_all(response.as_bytes()).await {
+                debug!("Cannot answer a metrics request: {}", e);
+            }
+            let _ = stream.shutdown().await;
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn metrics_are_rendered() {
+        let metrics = Metrics::default();
+        metrics.set_sessions(3);
+        metrics.set_tables(1, 2);
+        metrics.command("hello");
+        metrics.command("hello");
+        metrics.command("play");
+        metrics.error("not_your_turn");
+        metrics.move_played(Duration::from_millis(3));
+
+        let rendered = metrics.render();
+        assert!(rendered.contains("cirulla_sessions 3\n"));
+        assert!(rendered.contains("cirulla_tables{state=\"playing\"} 2\n"));
+        assert!(rendered.contains("cirulla_commands_total{command=\"hello\"} 2\n"));
+        assert!(rendered.contains("cirulla_errors_total{error=\"not_your_turn\"} 1\n"));
+        assert!(rendered.contains("cirulla_move_latency_seconds_bucket{le=\"0.001\"} 0\n"));
+        assert!(rendered.contains("cirulla_move_latency_seconds_bucket{le=\"0.005\"} 1\n"));
+        assert!(rendered.contains("cirulla_move_latency_seconds_count 1\n"));
+    }
+}
@@ -1,7 +1,18 @@
+mod admin;
+mod audit;
 mod command;
+mod config;
+mod heartbeat;
+mod lobby;
+mod metrics;
 mod response;
+#[allow(clippy::module_inception)]
 mod server;
 mod session;
+mod snapshot;
 mod table;
 
-pub use server::start_service;
+pub use audit::AuditConfig;
+pub use config::{ConfigError, Limits, Range, ServerConfig, TlsFiles};
+pub use heartbeat::HeartbeatConfig;
+pub use server::{start_service, Server, ServerHandle};
@@ -1,5 +1,8 @@
-use super::table::TableInfo;
+use super::config::Range;
+use super::table::{DealtSeed, TableInfo};
+use crate::i18n::Lang;
 use cirulla_lib::{GameError, GameForPlayer, HandResult};
+use std::fmt::Display;
 
 #[derive(Clone, Debug)]
 pub enum ServiceError {
@@ -11,12 +14,170 @@ pub enum ServiceError {
     InvalidCommand,
     GameError(GameError),
     NotYourTurn,
+    ServerFull,
+    TooManyTables,
+    InvalidName(usize, usize),
+    InvalidPlayerMax(Range),
+    InvalidWinAt(Range),
+    ChatRateLimited,
+    ChatTooLong(usize),
+    Silenced,
+    UserNotFound,
+    NotAdmin,
+    WrongPassword,
+    WaitingForPlayers,
+    InvalidEntropy,
+    InvalidLang,
+}
+
+impl ServiceError {
+    /// Short name of the error, as used in the metrics.
+    pub fn name(&self) -> &'static str {
+        match self {
+            ServiceError::NameInUse => "name_in_use",
+            ServiceError::NotHello => "not_hello",
+            ServiceError::TableNotFound => "table_not_found",
+            ServiceError::TableNameNotQuoted => "table_name_not_quoted",
+            ServiceError::TableAlreadyJoined => "table_already_joined",
+            ServiceError::InvalidCommand => "invalid_command",
+            ServiceError::GameError(_) => "game_error",
+            ServiceError::NotYourTurn => "not_your_turn",
+            ServiceError::ServerFull => "server_full",
+            ServiceError::TooManyTables => "too_many_tables",
+            ServiceError::InvalidName(_, _) => "invalid_name",
+            ServiceError::InvalidPlayerMax(_) => "invalid_player_max",
+            ServiceError::InvalidWinAt(_) => "invalid_win_at",
+            ServiceError::ChatRateLimited => "chat_rate_limited",
+            ServiceError::ChatTooLong(_) => "chat_too_long",
+            ServiceError::Silenced => "silenced",
+            ServiceError::UserNotFound => "user_not_found",
+            ServiceError::NotAdmin => "not_admin",
+            ServiceError::WrongPassword => "wrong_password",
+            ServiceError::WaitingForPlayers => "waiting_for_players",
+            ServiceError::InvalidEntropy => "invalid_entropy",
+            ServiceError::InvalidLang => "invalid_lang",
+        }
+    }
+}
+
+impl Display for ServiceError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let formatted_msg;
+        let message = match self {
+            ServiceError::NameInUse => "name already in use",
+            ServiceError::NotHello => "you need to say hello first",
+            ServiceError::TableNotFound => "table not found",
+            ServiceError::TableAlreadyJoined => "already joined a table",
+            ServiceError::TableNameNotQuoted => "table name must be quoted",
+            ServiceError::InvalidCommand => "invalid command",
+            ServiceError::GameError(err) => {
+                formatted_msg = format!("game error: {}", err);
+                &formatted_msg
+            }
+            ServiceError::NotYourTurn => "not your turn",
+            ServiceError::ServerFull => "server is full",
+            ServiceError::TooManyTables => "too many tables",
+            ServiceError::InvalidName(min, max) => {
+                formatted_msg = format!("name must be from {} to {} characters long", min, max);
+                &formatted_msg
+            }
+            ServiceError::InvalidPlayerMax(range) => {
+                formatted_msg = format!("players must be {}", range);
+                &formatted_msg
+            }
+            ServiceError::InvalidWinAt(range) => {
+                formatted_msg = format!("points to win must be {}", range);
+                &formatted_msg
+            }
+            ServiceError::ChatRateLimited => "too many messages, slow down",
+            ServiceError::ChatTooLong(max) => {
+                formatted_msg = format!("message longer than {} characters", max);
+                &formatted_msg
+            }
+            ServiceError::Silenced => "you have been silenced",
+            ServiceError::UserNotFound => "user not found",
+            ServiceError::NotAdmin => "you are not an admin",
+            ServiceError::WrongPassword => "wrong password",
+            ServiceError::WaitingForPlayers => "waiting for the other players to come back",
+            ServiceError::InvalidEntropy => "entropy must be up to 64 hexadecimal digits",
+            ServiceError::InvalidLang => "language must be one of: it, en",
+        };
+        write!(f, "{}", message)
+    }
+}
+
+impl ServiceError {
+    /// The error told in `lang`.
+    pub fn text(&self, lang: Lang) -> String {
+        match lang {
+            Lang::En => self.to_string(),
+            Lang::It => self.italian(),
+        }
+    }
+
+    fn italian(&self) -> String {
+        let message = match self {
+            ServiceError::NameInUse => "nome già in uso",
+            ServiceError::NotHello => "prima bisogna presentarsi con HELLO",
+            ServiceError::TableNotFound => "tavolo non trovato",
+            ServiceError::TableAlreadyJoined => "già seduto a un tavolo",
+            ServiceError::TableNameNotQuoted => "il nome del tavolo va tra virgolette",
+            ServiceError::InvalidCommand => "comando non valido",
+            ServiceError::GameError(err) => {
+                return format!("errore di gioco: {}", (Lang::It.texts().game_error)(err))
+            }
+            ServiceError::NotYourTurn => "non è il tuo turno",
+            ServiceError::ServerFull => "il server è pieno",
+            ServiceError::TooManyTables => "troppi tavoli",
+            ServiceError::InvalidName(min, max) => {
+                return format!("il nome deve essere lungo da {} a {} caratteri", min, max)
+            }
+            ServiceError::InvalidPlayerMax(range) => {
+                return format!("i giocatori devono essere {}", range)
+            }
+            ServiceError::InvalidWinAt(range) => {
+                return format!("i punti per vincere devono essere {}", range)
+            }
+            ServiceError::ChatRateLimited => "troppi messaggi, rallenta",
+            ServiceError::ChatTooLong(max) => {
+                return format!("messaggio più lungo di {} caratteri", max)
+            }
+            ServiceError::Silenced => "sei stato zittito",
+            ServiceError::UserNotFound => "utente non trovato",
+            ServiceError::NotAdmin => "non sei un admin",
+            ServiceError::WrongPassword => "password errata",
+            ServiceError::WaitingForPlayers => "in attesa che tornino gli altri giocatori",
+            ServiceError::InvalidEntropy => "l'entropia deve avere fino a 64 cifre esadecimali",
+            ServiceError::InvalidLang => "la lingua deve essere una tra: it, en",
+        };
+        message.to_string()
+    }
+}
+
+/// What a named session is doing, as reported by WHO.
+#[derive(Clone)]
+pub enum UserState {
+    Lobby,
+    Waiting(u8),
+    Playing(u8),
+}
+
+impl Display for UserState {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            UserState::Lobby => write!(f, "LOBBY"),
+            UserState::Waiting(table_id) => write!(f, "WAITING {}", table_id),
+            UserState::Playing(table_id) => write!(f, "PLAYING {}", table_id),
+        }
+    }
 }
 
 #[derive(Clone)]
 pub enum Response {
     Hi(String),
     Scream((String, String)),
+    TableSay((String, String)),
+    Whisper((String, String)),
     Error(ServiceError),
     TableCreated(TableInfo),
     TableJoined(u8),
@@ -27,33 +188,48 @@ pub enum Response {
     GameStatus(GameForPlayer),
     GameEnd,
     HandResult(HandResult),
+    SeedCommit(String),
+    SeedReveal(DealtSeed),
     Play,
     Wait,
     Status((String, u8)),
+    Who(Vec<(String, UserState)>),
+    UserJoined(String),
+    Announce(String),
+    Shutdown,
+    UserLeft(String),
+    Ping,
+    Motd(String),
+    Muted(String),
+    Unmuted(String),
+    Admin,
+    Silenced(String),
+    Unsilenced(String),
+    Kicked(String),
+    Lang(Lang),
 }
 
-impl ToString for Response {
-    fn to_string(&self) -> String {
-        let formatted_msg;
+impl Response {
+    /// The response as sent to a session speaking `lang`: only the texts
+    /// meant for people change, the protocol stays the same.
+    pub fn render(&self, lang: Lang) -> String {
         match self {
+            Response::Error(code) => format!("ERROR: {}\n", code.text(lang)),
+            _ => self.to_string(),
+        }
+    }
+}
+
+impl Display for Response {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let output = match self {
             Response::Hi(name) => format!("HI {}\n", name),
             Response::Scream((name, message)) => format!("SCREAM FROM {}: {}\n", name, message),
-            Response::Error(code) => format!(
-                "ERROR: {}\n",
-                match code {
-                    ServiceError::NameInUse => "name already in use",
-                    ServiceError::NotHello => "you need to say hello first",
-                    ServiceError::TableNotFound => "table not found",
-                    ServiceError::TableAlreadyJoined => "already joined a table",
-                    ServiceError::TableNameNotQuoted => "table name must be quoted",
-                    ServiceError::InvalidCommand => "invalid command",
-                    ServiceError::GameError(err) => {
-                        formatted_msg = format!("game error: {}", err);
-                        &formatted_msg
-                    },
-                    ServiceError::NotYourTurn => "not your turn",
-                }
-            ),
+            Response::TableSay((name, message)) => {
+                format!("TABLE SAY FROM {}: {}\n", name, message)
+            }
+            Response::Whisper((name, message)) => format!("WHISPER FROM {}: {}\n", name, message),
+            Response::Error(code) => format!("ERROR: {}\n", code),
             Response::TableCreated(info) => format!(
                 "TABLE CREATED {} \"{}\" {}/{} {}\n",
                 info.id, info.name, info.player_count, info.player_max, info.win_at
@@ -72,6 +248,27 @@ impl ToString for Response {
                 "HAND RESULT START\n{}\nHAND RESULT END\n",
                 serde_json::to_string_pretty(result).expect("Should serialize")
             ),
+            Response::SeedCommit(commitment) => format!("SEED COMMIT {}\n", commitment),
+            Response::SeedReveal(dealt) => {
+                let mut response = format!("SEED REVEAL {}", dealt.seed);
+                for entropy in dealt.entropy.iter() {
+                    response.push_str(&format!(" {}", entropy));
+                }
+                response.push('\n');
+                response
+            }
+            Response::Who(list) => {
+                let mut response = "WHO START\n".to_string();
+                for (name, state) in list {
+                    response.push_str(&format!("{} {}\n", state, name));
+                }
+                response.push_str("WHO END\n");
+                response
+            }
+            Response::UserJoined(name) => format!("USER JOINED {}\n", name),
+            Response::Announce(message) => format!("ANNOUNCE {}\n", message),
+            Response::Shutdown => "SHUTDOWN\n".to_string(),
+            Response::UserLeft(name) => format!("USER LEFT {}\n", name),
             Response::TableList(list) => {
                 let mut response = "TABLE LIST START\n".to_string();
                 for table in list {
@@ -89,6 +286,16 @@ impl ToString for Response {
                 name, table_id
             ),
             Response::GameEnd => "GAME END\n".to_string(),
-        }
+            Response::Ping => "PING\n".to_string(),
+            Response::Motd(message) => format!("MOTD {}\n", message),
+            Response::Muted(name) => format!("MUTED {}\n", name),
+            Response::Unmuted(name) => format!("UNMUTED {}\n", name),
+            Response::Admin => "ADMIN OK\n".to_string(),
+            Response::Silenced(name) => format!("SILENCED {}\n", name),
+            Response::Unsilenced(name) => format!("UNSILENCED {}\n", name),
+            Response::Kicked(name) => format!("KICKED {}\n", name),
+            Response::Lang(lang) => format!("LANG {}\n", lang),
+        };
+        write!(f, "{}", output)
     }
 }
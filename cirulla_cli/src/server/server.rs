@@ -1,454 +1,383 @@
-use super::command::Command;
-use super::response::{Response, ServiceError};
-use super::session::{Session, SessionCommand};
-use super::table::Table;
-use cirulla_lib::NextAction;
-use log::{debug, info, warn};
-use std::collections::HashMap;
-use std::sync::{mpsc::channel, Arc, Mutex};
-use std::{net::TcpListener, thread};
-
-struct Server {
-    sessions: HashMap<String, Session>,
-    tables: HashMap<u8, Table>,
+use super::admin;
+use super::audit::AuditLog;
+use super::config::ServerConfig;
+use super::heartbeat::HeartbeatConfig;
+use super::lobby::{Lobby, LobbyMessage};
+use super::metrics::Metrics;
+use super::session::Session;
+use super::snapshot;
+use log::{info, warn};
+use std::io;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::Arc;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::TcpListener;
+use tokio::runtime::Runtime;
+use tokio::sync::mpsc::{unbounded_channel, UnboundedSender};
+use tokio::task::JoinHandle;
+use tokio::time::{interval_at, timeout, Instant};
+use tokio_rustls::TlsAcceptor;
+use tokio_util::{sync::CancellationToken, task::TaskTracker};
+
+/// Runs a server until it is shut down by a signal or by an admin, reloading
+/// its configuration from `config_path` on SIGHUP.
+pub fn start_service(
+    config: ServerConfig,
+    config_path: Option<PathBuf>,
+    tls: Option<TlsAcceptor>,
+) -> io::Result<()> {
+    let runtime = Runtime::new().expect("Failed to start the async runtime");
+    runtime.block_on(async {
+        let handle = Server::bind(config, tls).await?.spawn();
+        shutdown_on_signal(handle.lobby_sender.clone());
+        if let Some(path) = config_path {
+            reload_on_hangup(path, handle.lobby_sender.clone());
+        }
+        handle.wait().await;
+        Ok(())
+    })
 }
 
-impl Server {
-    pub fn new() -> Server {
-        Server {
-            sessions: HashMap::new(),
-            tables: HashMap::new(),
-        }
-    }
+/// A server listening on every address of its configuration, not yet
+/// accepting connections.
+pub struct Server {
+    config: ServerConfig,
+    tls: Option<TlsAcceptor>,
+    listeners: Vec<TcpListener>,
+    metrics_listener: Option<TcpListener>,
+    audit: AuditLog,
+}
 
-    pub fn register_session(&mut self, session: Session) -> &Session {
-        let id = session.id.clone();
-        debug!("Registering session {}", id);
-        self.sessions.insert(id.clone(), session);
-        self.sessions.get(&id).unwrap()
-    }
+/// A server running in the background.
+pub struct ServerHandle {
+    local_addrs: Vec<SocketAddr>,
+    metrics_addr: Option<SocketAddr>,
+    lobby_sender: UnboundedSender<LobbyMessage>,
+    task: JoinHandle<()>,
+}
 
-    fn unregister_session(&mut self, id: &str) {
-        debug!("Unregistering session {}", id);
-        match self.find_table(id) {
-            Some(table_id) => {
-                self.tables.remove(&table_id);
-                self.broadcast(Response::TableRemoved(table_id));
-            }
-            None => {}
+impl Server {
+    /// Binds every address in `config.listen`; port 0 picks a free port,
+    /// which can then be read from `local_addrs`.
+    pub async fn bind(config: ServerConfig, tls: Option<TlsAcceptor>) -> io::Result<Server> {
+        let mut listeners = Vec::new();
+        for address in config.listen.iter() {
+            let listener = TcpListener::bind(address).await.map_err(|e| {
+                io::Error::new(e.kind(), format!("cannot bind to {}: {}", address, e))
+            })?;
+            info!(
+                "Listening on {}{}",
+                listener.local_addr()?,
+                if tls.is_some() { " with TLS" } else { "" }
+            );
+            listeners.push(listener);
         }
-        self.sessions.get(id).unwrap().disconnect();
-        self.sessions.remove(id);
-    }
-
-    pub fn execute(&mut self, session_id: &str, command: Command) {
-        match command {
-            Command::Error(message) => {
-                self.error(session_id, message);
-            }
-            Command::Hello(name) => {
-                self.hello(session_id, name);
-            }
-            Command::Scream(message) => {
-                self.scream(session_id, message);
+        let metrics_listener = Server::bind_metrics(&config).await?;
+        let audit = match &config.audit {
+            Some(audit) => {
+                info!("Audit log written to {}", audit.path.display());
+                AuditLog::open(audit)?
             }
-            Command::Quit => {
-                self.unregister_session(session_id);
-            }
-            Command::TableNew((name, player_max, win_at)) => {
-                self.table_new(session_id, name, player_max, win_at);
-            }
-            Command::TableList => {
-                self.table_list(session_id);
-            }
-            Command::TableJoin(table_id) => {
-                self.table_join(session_id, table_id);
-            }
-            Command::TableLeave => {
-                self.table_leave(session_id);
-            }
-            Command::Status => {
-                self.status(session_id);
-            }
-            Command::Play(card) => {
-                self.play(session_id, card);
-            }
-        }
-    }
-
-    fn play(&mut self, session_id: &str, card: String) {
-        let table_id = self.find_table(session_id).unwrap_or_default();
-
-        match self.tables.get_mut(&table_id) {
-            Some(table) => {
-                let player_id = table
-                    .sessions_players
-                    .get(session_id)
-                    .expect("Invalid session ID");
-
-                if player_id != &table.game.current_player().id {
-                    self.error(session_id, ServiceError::NotYourTurn);
-                    return;
-                }
-
-                match table.game.player_play(&card) {
-                    Ok(_) => {}
-                    Err(e) => {
-                        self.error(session_id, ServiceError::GameError(e));
-                        return;
-                    }
-                }
-                let next_action = table.game.next_round_action();
-                debug!("Next action: {:?}", next_action);
-
-                if match next_action {
-                    NextAction::NextPlayer => true,
-                    NextAction::NextRound => {
-                        table.game.start_round().unwrap();
-                        true
-                    }
-                    NextAction::EndHand => {
-                        let result = table.game.end_hand().unwrap();
-                        let someone_wins = result.someone_wins;
-                        table
-                            .sessions_players
-                            .iter()
-                            .for_each(|(session_id, _player_id)| {
-                                let session = self
-                                    .sessions
-                                    .get_mut(session_id)
-                                    .expect("Invalid session ID");
-                                session.send_response(Response::HandResult(result.clone()));
-                            });
-
-                        if someone_wins {
-                            table
-                                .sessions_players
-                                .iter()
-                                .for_each(|(session_id, _player_id)| {
-                                    let session = self
-                                        .sessions
-                                        .get_mut(session_id)
-                                        .expect("Invalid session ID");
-                                    session.send_response(Response::GameEnd);
-                                });
-                                false
-                        } else {
-                            table.game.start_hand().unwrap();
-                            true
-                        }
-                    }
-                } {
-
-                let active_player = table.game.current_player().id.clone();
-                table
-                    .sessions_players
-                    .iter()
-                    .for_each(|(session_id, player_id)| {
-                        let session = self
-                            .sessions
-                            .get_mut(session_id)
-                            .expect("Invalid session ID");
-                        session.send_response(Response::GameStatus(
-                            table.game.as_game_for_player(player_id),
-                        ));
-                        if player_id == &active_player {
-                            session.send_response(Response::Play);
-                        } else {
-                            session.send_response(Response::Wait);
-                        }
-                    });
-                }
-            }
-            None => {
-                self.error(session_id, ServiceError::TableNotFound);
-            }
-        }
+            None => AuditLog::default(),
+        };
+        Ok(Server {
+            config,
+            tls,
+            listeners,
+            metrics_listener,
+            audit,
+        })
     }
 
-    fn status(&mut self, session_id: &str) {
-        let session = self
-            .sessions
-            .get_mut(session_id)
-            .expect("Invalid session ID");
-
-        let name = match &session.name {
-            Some(name) => name.clone(),
-            None => "".to_string(),
+    #[cfg(feature = "metrics")]
+    async fn bind_metrics(config: &ServerConfig) -> io::Result<Option<TcpListener>> {
+        let address = match &config.metrics {
+            Some(address) => address,
+            None => return Ok(None),
         };
-        let table = self
-            .tables
-            .values()
-            .find(|t| t.sessions_players.contains_key(session_id))
-            .map(|t| t.id)
-            .unwrap_or(0);
-
-        session.send_response(Response::Status((name, table)));
+        let listener = TcpListener::bind(address).await.map_err(|e| {
+            io::Error::new(
+                e.kind(),
+                format!("cannot bind metrics to {}: {}", address, e),
+            )
+        })?;
+        info!(
+            "Metrics available on http://{}/metrics",
+            listener.local_addr()?
+        );
+        Ok(Some(listener))
     }
 
-    fn table_list(&mut self, session_id: &str) {
-        let tables = self.tables.values().map(|t| t.as_info()).collect();
-        self.sessions
-            .get_mut(session_id)
-            .expect("Invalid session ID")
-            .send_response(Response::TableList(tables));
+    #[cfg(not(feature = "metrics"))]
+    async fn bind_metrics(config: &ServerConfig) -> io::Result<Option<TcpListener>> {
+        if config.metrics.is_some() {
+            warn!("This server was built without metrics: ignoring the metrics address");
+        }
+        Ok(None)
     }
 
-    fn table_join(&mut self, session_id: &str, table_id: u8) {
-        if self
-            .tables
+    pub fn local_addrs(&self) -> Vec<SocketAddr> {
+        self.listeners
             .iter()
-            .any(|(_, t)| t.sessions_players.contains_key(session_id))
-        {
-            self.error(session_id, ServiceError::TableAlreadyJoined);
-            return;
-        }
-
-        match self.tables.get_mut(&table_id) {
-            Some(table) => {
-                let session = self
-                    .sessions
-                    .get_mut(session_id)
-                    .expect("Invalid session ID");
-
-                let player_name = match session.name {
-                    Some(ref name) => name.clone(),
-                    None => {
-                        self.error(session_id, ServiceError::NotHello);
-                        return;
-                    }
-                };
+            .filter_map(|listener| listener.local_addr().ok())
+            .collect()
+    }
 
-                match table.add_session(session.id.clone(), player_name) {
-                    Ok(_) => {
-                        session.send_response(Response::TableJoined(table_id));
-                        session.send_response(Response::Wait);
-                        self.maybe_table_start_game(table_id);
-                    }
-                    Err(e) => {
-                        self.error(session_id, e);
+    /// Starts accepting connections on the current tokio runtime.
+    pub fn spawn(self) -> ServerHandle {
+        let local_addrs = self.local_addrs();
+        let metrics_addr = self
+            .metrics_listener
+            .as_ref()
+            .and_then(|listener| listener.local_addr().ok());
+        let heartbeat = self.config.heartbeat;
+        let metrics = Arc::new(Metrics::default());
+        let (lobby_sender, lobby_receiver) = unbounded_channel::<LobbyMessage>();
+        let shutdown = CancellationToken::new();
+        let writers = TaskTracker::new();
+
+        let mut lobby = Lobby::new(
+            lobby_sender.clone(),
+            &self.config,
+            shutdown.clone(),
+            metrics.clone(),
+            self.audit,
+        );
+        if let Some(path) = self.config.state_file.as_ref().filter(|path| path.exists()) {
+            match snapshot::load(path) {
+                Ok(snapshots) => {
+                    lobby.restore(snapshots);
+                    // Restored once: a crash must not bring back stale games
+                    if let Err(e) = std::fs::remove_file(path) {
+                        warn!("Cannot remove {}: {}", path.display(), e);
                     }
                 }
-            }
-            None => {
-                self.error(session_id, ServiceError::TableNotFound);
+                Err(e) => warn!("Cannot restore tables from {}: {}", path.display(), e),
             }
         }
-    }
-
-    fn maybe_table_start_game(&mut self, table_id: u8) {
-        match self.tables.get_mut(&table_id) {
-            Some(table) => {
-                if (table.sessions_players.len() as u8) == table.player_max {
-                    match table.game.start_game() {
-                        Ok(_) => {
-                            if let Err(e) = table.game.start_hand() {
-                                table.sessions_players.iter().for_each(|(session_id, _)| {
-                                    self.sessions
-                                        .get_mut(session_id)
-                                        .expect("Invalid session ID")
-                                        .send_response(Response::Error(ServiceError::GameError(
-                                            e.clone(),
-                                        )));
-                                });
-                                return;
-                            }
-                            if let Err(e) = table.game.start_round() {
-                                table.sessions_players.iter().for_each(|(session_id, _)| {
-                                    self.sessions
-                                        .get_mut(session_id)
-                                        .expect("Invalid session ID")
-                                        .send_response(Response::Error(ServiceError::GameError(
-                                            e.clone(),
-                                        )));
-                                });
-                                return;
-                            }
-                            let active_player = table.game.current_player().id.clone();
-                            table
-                                .sessions_players
-                                .iter()
-                                .for_each(|(session_id, player_id)| {
-                                    let session = self
-                                        .sessions
-                                        .get_mut(session_id)
-                                        .expect("Invalid session ID");
-                                    session.send_response(Response::GameStart(table_id));
-                                    session.send_response(Response::GameStatus(
-                                        table.game.as_game_for_player(player_id),
-                                    ));
-                                    if player_id == &active_player {
-                                        session.send_response(Response::Play);
-                                    } else {
-                                        session.send_response(Response::Wait);
-                                    }
-                                });
-                        }
-                        Err(e) => self.broadcast(Response::Error(ServiceError::GameError(e))),
-                    };
+        tokio::spawn(lobby.run(lobby_receiver));
+
+        let ping_sender = lobby_sender.clone();
+        let ping_shutdown = shutdown.clone();
+        tokio::spawn(async move {
+            let start = Instant::now() + heartbeat.ping_interval;
+            let mut ticks = interval_at(start, heartbeat.ping_interval);
+            loop {
+                tokio::select! {
+                    _ = ping_shutdown.cancelled() => break,
+                    _ = ticks.tick() => {}
+                }
+                if ping_sender.send(LobbyMessage::Ping).is_err() {
+                    break;
                 }
             }
-            None => {
-                self.broadcast(Response::Error(ServiceError::TableNotFound));
-            }
-        }
-    }
+        });
 
-    fn table_new(&mut self, session_id: &str, name: String, player_max: u8, win_at: u8) {
-        if self
-            .tables
-            .iter()
-            .any(|(_, t)| t.sessions_players.contains_key(session_id))
-        {
-            self.error(session_id, ServiceError::TableAlreadyJoined);
-            return;
+        if let Some(path) = self.config.admin_socket {
+            admin::listen(path, lobby_sender.clone(), shutdown.clone());
         }
 
-        let session = self
-            .sessions
-            .get_mut(session_id)
-            .expect("Invalid session ID");
-
-        let player_name = match session.name {
-            Some(ref name) => name.clone(),
-            None => {
-                self.error(session_id, ServiceError::NotHello);
-                return;
-            }
-        };
-
-        let mut table = Table::new(name, player_max, win_at);
+        #[cfg(feature = "metrics")]
+        if let Some(listener) = self.metrics_listener {
+            tokio::spawn(super::metrics::serve(
+                listener,
+                metrics.clone(),
+                shutdown.clone(),
+            ));
+        }
 
-        match table.add_session(session.id.clone(), player_name) {
-            Ok(_) => {
-                let table_id = table.id.clone();
-                self.tables.insert(table_id.clone(), table);
-                let table = self.tables.get(&table_id).unwrap();
+        for listener in self.listeners {
+            tokio::spawn(accept_connections(
+                listener,
+                self.tls.clone(),
+                lobby_sender.clone(),
+                heartbeat,
+                shutdown.clone(),
+                writers.clone(),
+                metrics.clone(),
+            ));
+        }
 
-                session.send_response(Response::TableJoined(table_id));
-                session.send_response(Response::Wait);
-                self.broadcast(Response::TableCreated(table.as_info()));
-            }
-            Err(e) => {
-                self.error(session_id, e);
+        let task = tokio::spawn(async move {
+            shutdown.cancelled().await;
+            // Give the clients a chance to read the last responses
+            writers.close();
+            if timeout(heartbeat.write_timeout, writers.wait())
+                .await
+                .is_err()
+            {
+                warn!("Some sessions did not receive their last responses");
             }
+            info!("Server stopped");
+        });
+
+        ServerHandle {
+            local_addrs,
+            metrics_addr,
+            lobby_sender,
+            task,
         }
     }
+}
 
-    fn find_table(&self, session_id: &str) -> Option<u8> {
-        match self
-            .tables
-            .values()
-            .find(|t| t.sessions_players.contains_key(session_id))
-        {
-            Some(table) => Some(table.id),
-            None => None,
-        }
+impl ServerHandle {
+    pub fn local_addrs(&self) -> &[SocketAddr] {
+        &self.local_addrs
     }
 
-    fn table_leave(&mut self, session_id: &str) {
-        if let Some(table_id) = self.find_table(session_id) {
-            match self.tables.get_mut(&table_id) {
-                Some(table) => match table.remove_session(session_id) {
-                    Ok(_) => {
-                        self.sessions
-                            .get_mut(session_id)
-                            .expect("Invalid session ID")
-                            .send_response(Response::TableLeaved(table_id));
-                        if table.sessions_players.is_empty() {
-                            self.tables.remove(&table_id);
-                            self.broadcast(Response::TableRemoved(table_id));
-                        }
-                    }
-                    Err(e) => {
-                        self.error(session_id, e);
-                    }
-                },
-                None => {
-                    self.error(session_id, ServiceError::TableNotFound);
-                }
-            };
-        }
+    /// Address of the metrics endpoint, when it is enabled.
+    pub fn metrics_addr(&self) -> Option<SocketAddr> {
+        self.metrics_addr
     }
 
-    fn error(&mut self, session_id: &str, code: ServiceError) {
-        self.sessions
-            .get_mut(session_id)
-            .expect("Invalid session ID")
-            .send_response(Response::Error(code));
+    /// Shuts the server down as SIGTERM would, and waits for it to stop.
+    pub async fn stop(self) {
+        let _ = self.lobby_sender.send(LobbyMessage::Shutdown);
+        self.wait().await;
     }
 
-    fn hello(&mut self, session_id: &str, name: String) {
-        let existent = self
-            .sessions
-            .values()
-            .filter(|s| s.name == Some(name.clone()))
-            .count();
-
-        let session = self
-            .sessions
-            .get_mut(session_id)
-            .expect("Invalid session ID");
-
-        if existent > 0 {
-            session.send_response(Response::Error(ServiceError::NameInUse));
-            return;
+    /// Waits until the server has been shut down.
+    pub async fn wait(self) {
+        if let Err(e) = self.task.await {
+            warn!("Server stopped abruptly: {}", e);
         }
-
-        session.name = Some(name.clone());
-        session.send_response(Response::Hi(name.clone()));
     }
+}
 
-    fn scream(&mut self, session_id: &str, message: String) {
-        let session = self.sessions.get(session_id).expect("Invalid session ID");
-        match &session.name {
-            Some(name) => self.broadcast(Response::Scream((name.clone(), message.clone()))),
-            None => self.error(session_id, ServiceError::NotHello),
+async fn accept_connections(
+    listener: TcpListener,
+    tls: Option<TlsAcceptor>,
+    lobby_sender: UnboundedSender<LobbyMessage>,
+    heartbeat: HeartbeatConfig,
+    shutdown: CancellationToken,
+    writers: TaskTracker,
+    metrics: Arc<Metrics>,
+) {
+    loop {
+        let accepted = tokio::select! {
+            _ = shutdown.cancelled() => break,
+            accepted = listener.accept() => accepted,
+        };
+        match accepted {
+            Ok((tcp_stream, peer)) => {
+                let id = peer.to_string();
+                match &tls {
+                    None => register(
+                        tcp_stream,
+                        id,
+                        &lobby_sender,
+                        &heartbeat,
+                        &writers,
+                        &metrics,
+                    ),
+                    Some(acceptor) => {
+                        let acceptor = acceptor.clone();
+                        let lobby_sender = lobby_sender.clone();
+                        let writers = writers.clone();
+                        let metrics = metrics.clone();
+                        tokio::spawn(async move {
+                            let handshake = acceptor.accept(tcp_stream);
+                            match timeout(heartbeat.idle_timeout, handshake).await {
+                                Ok(Ok(tls_stream)) => register(
+                                    tls_stream,
+                                    id,
+                                    &lobby_sender,
+                                    &heartbeat,
+                                    &writers,
+                                    &metrics,
+                                ),
+                                Ok(Err(e)) => warn!("TLS handshake with {} failed: {}", id, e),
+                                Err(_) => warn!("TLS handshake with {} timed out", id),
+                            }
+                        });
+                    }
+                }
+            }
+            Err(e) => {
+                warn!("Failed to accept connection: {}", e);
+            }
         }
     }
+}
 
-    fn broadcast(&mut self, message: Response) {
-        for session in self.sessions.values_mut() {
-            session.send_response(message.clone());
-        }
+fn register<S>(
+    stream: S,
+    id: String,
+    lobby_sender: &UnboundedSender<LobbyMessage>,
+    heartbeat: &HeartbeatConfig,
+    writers: &TaskTracker,
+    metrics: &Arc<Metrics>,
+) where
+    S: AsyncRead + AsyncWrite + Send + Unpin + 'static,
+{
+    let session = Session::new(
+        stream,
+        id,
+        lobby_sender.clone(),
+        heartbeat,
+        writers,
+        metrics.clone(),
+    );
+    if lobby_sender.send(LobbyMessage::Register(session)).is_err() {
+        warn!("The lobby has stopped");
     }
 }
 
-pub fn start_service(address: String, port: u16) {
-    let listener = TcpListener::bind(format!("{}:{}", address, port));
-    let server = Arc::new(Mutex::new(Server::new()));
-
-    let (command_sender, command_receiver) = channel::<SessionCommand>();
+/// Turns SIGINT and, on Unix, SIGTERM into a graceful shutdown.
+fn shutdown_on_signal(lobby_sender: UnboundedSender<LobbyMessage>) {
+    tokio::spawn(async move {
+        #[cfg(unix)]
+        let terminate = async {
+            use tokio::signal::unix::{signal, SignalKind};
+            match signal(SignalKind::terminate()) {
+                Ok(mut terminations) => {
+                    terminations.recv().await;
+                }
+                Err(e) => {
+                    warn!("Cannot listen for SIGTERM: {}", e);
+                    std::future::pending::<()>().await;
+                }
+            }
+        };
+        #[cfg(not(unix))]
+        let terminate = std::future::pending::<()>();
 
-    let server_clone = server.clone();
-    thread::spawn(move || {
-        for (session_id, command) in command_receiver {
-            server_clone.lock().unwrap().execute(&session_id, command);
+        tokio::select! {
+            _ = tokio::signal::ctrl_c() => info!("Received SIGINT"),
+            _ = terminate => info!("Received SIGTERM"),
         }
+        let _ = lobby_sender.send(LobbyMessage::Shutdown);
     });
+}
 
-    match listener {
-        Ok(listener) => {
-            info!("Listening on {}:{}", address, port);
+#[cfg(unix)]
+fn reload_on_hangup(path: PathBuf, lobby_sender: UnboundedSender<LobbyMessage>) {
+    use tokio::signal::unix::{signal, SignalKind};
 
-            for incoming_stream in listener.incoming() {
-                match incoming_stream {
-                    Ok(tcp_stream) => {
-                        let session = Session::new(tcp_stream, command_sender.clone());
-                        server
-                            .lock()
-                            .unwrap()
-                            .register_session(session)
-                            .read_commands();
-                    }
-                    Err(e) => {
-                        warn!("Failed to accept connection: {}", e.to_string());
+    let mut hangups = match signal(SignalKind::hangup()) {
+        Ok(hangups) => hangups,
+        Err(e) => {
+            warn!("Cannot listen for SIGHUP: {}", e);
+            return;
+        }
+    };
+
+    tokio::spawn(async move {
+        while hangups.recv().await.is_some() {
+            match ServerConfig::from_file(&path) {
+                Ok(config) => {
+                    if lobby_sender.send(LobbyMessage::Reload(config)).is_err() {
+                        break;
                     }
                 }
+                Err(e) => warn!("Keeping the current configuration: {}", e),
             }
         }
-        Err(e) => {
-            panic!("Failed to bind to {}:{}: {}", address, port, e.to_string());
-        }
-    }
+    });
+}
+
+#[cfg(not(unix))]
+fn reload_on_hangup(_path: PathBuf, _lobby_sender: UnboundedSender<LobbyMessage>) {
+    warn!("Reloading the configuration is only supported on Unix");
 }
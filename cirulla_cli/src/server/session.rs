@@ -1,66 +1,164 @@
-use super::{command::Command, response::Response};
-use log::{info, warn};
+use super::{
+    command::Command, heartbeat::HeartbeatConfig, lobby::LobbyMessage, metrics::Metrics,
+    response::Response,
+};
+use crate::i18n::Lang;
+use log::{debug, info, warn};
 use std::{
-    io::{prelude::*, BufReader},
-    net::TcpStream,
-    sync::mpsc::Sender,
-    thread,
+    collections::{HashSet, VecDeque},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use tokio::{
+    io::{split, AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader},
+    select,
+    sync::mpsc::{channel, error::TrySendError, Receiver, Sender, UnboundedSender},
+    time::timeout,
 };
+use tokio_util::{sync::CancellationToken, task::TaskTracker};
 
-pub type SessionCommand = (String, Command);
+/// Maximum number of responses waiting to be written to a single client.
+/// A client that falls this far behind is disconnected.
+const OUTBOX_SIZE: usize = 256;
+
+type Reader = Box<dyn AsyncRead + Send + Unpin>;
+type Writer = Box<dyn AsyncWrite + Send + Unpin>;
 
 pub struct Session {
     pub id: String,
     pub name: Option<String>,
-    command_sender: Sender<SessionCommand>,
-    stream: TcpStream,
+    pub table: Option<u8>,
+    pub admin: bool,
+    pub silenced: bool,
+    /// Names whose chat messages this session does not want to receive
+    pub muted: HashSet<String>,
+    pub chat_violations: usize,
+    command_sender: UnboundedSender<LobbyMessage>,
+    handle: SessionHandle,
+    reader: Option<Reader>,
+    idle_timeout: Duration,
+    chat_sent: VecDeque<Instant>,
+}
+
+/// The writing side of a session, shared between the lobby and the table
+/// the session is sitting at.
+#[derive(Clone)]
+pub struct SessionHandle {
+    pub id: String,
+    outbox: Sender<Response>,
+    cancel: CancellationToken,
+    metrics: Arc<Metrics>,
 }
 
 impl Session {
-    pub fn new(stream: TcpStream, command_sender: Sender<SessionCommand>) -> Session {
-        let id = stream.peer_addr().unwrap().to_string();
+    pub fn new<S>(
+        stream: S,
+        id: String,
+        command_sender: UnboundedSender<LobbyMessage>,
+        heartbeat: &HeartbeatConfig,
+        writers: &TaskTracker,
+        metrics: Arc<Metrics>,
+    ) -> Session
+    where
+        S: AsyncRead + AsyncWrite + Send + Unpin + 'static,
+    {
         info!("New connection from {}", id);
 
+        let (reader, writer) = split(stream);
+        let (outbox, outbox_receiver) = channel::<Response>(OUTBOX_SIZE);
+        let cancel = CancellationToken::new();
+        writers.spawn(SessionHandle::write_responses(
+            id.clone(),
+            Box::new(writer),
+            outbox_receiver,
+            cancel.clone(),
+            heartbeat.write_timeout,
+        ));
+
         Session {
-            id,
+            id: id.clone(),
             name: None,
-            stream,
+            table: None,
+            admin: false,
+            silenced: false,
+            muted: HashSet::new(),
+            chat_violations: 0,
             command_sender,
+            handle: SessionHandle {
+                id,
+                outbox,
+                cancel,
+                metrics,
+            },
+            reader: Some(Box::new(reader)),
+            idle_timeout: heartbeat.idle_timeout,
+            chat_sent: VecDeque::new(),
+        }
+    }
+
+    /// Records a chat message, unless the session already sent `messages`
+    /// of them in the last `interval`.
+    pub fn try_chat(&mut self, messages: usize, interval: Duration) -> bool {
+        let now = Instant::now();
+        while let Some(sent) = self.chat_sent.front() {
+            if now.duration_since(*sent) < interval {
+                break;
+            }
+            self.chat_sent.pop_front();
+        }
+        if self.chat_sent.len() >= messages {
+            return false;
         }
+        self.chat_sent.push_back(now);
+        true
     }
 
-    pub fn send_response(&mut self, message: Response) {
-        self.stream
-            .write_all(message.to_string().as_bytes())
-            .unwrap();
+    pub fn handle(&self) -> SessionHandle {
+        self.handle.clone()
+    }
+
+    pub fn send_response(&self, message: Response) {
+        self.handle.send_response(message);
     }
 
     pub fn disconnect(&self) {
-        info!("Disconnecting session {}", self.id);
-        self.stream.shutdown(std::net::Shutdown::Both).unwrap();
+        self.handle.disconnect();
     }
 
-    pub fn read_commands(&self) {
+    pub fn read_commands(&mut self) {
+        let reader = match self.reader.take() {
+            Some(reader) => reader,
+            None => {
+                warn!("Session {} is already reading", self.id);
+                return;
+            }
+        };
         let sender = self.command_sender.clone();
         let session_id = self.id.clone();
-        let stream = self
-            .stream
-            .try_clone()
-            .expect("Failed to clone reading stream");
-        let mut reader = BufReader::new(stream);
+        let cancel = self.handle.cancel.clone();
+        let idle_timeout = self.idle_timeout;
+        let mut reader = BufReader::new(reader);
 
-        thread::spawn(move || {
+        tokio::spawn(async move {
             loop {
                 let mut incoming: Vec<u8> = vec![];
 
-                match reader.read_until(b'\n', &mut incoming) {
-                    Ok(num_bytes_read) => {
+                let read = select! {
+                    _ = cancel.cancelled() => break,
+                    read = timeout(idle_timeout, reader.read_until(b'\n', &mut incoming)) => read,
+                };
+                match read {
+                    Ok(Ok(num_bytes_read)) => {
                         if num_bytes_read == 0 {
                             break;
                         }
                     }
-                    Err(e) => {
-                        warn!("Failed to read from stream: {}", e.to_string());
+                    Ok(Err(e)) => {
+                        warn!("Failed to read from stream: {}", e);
+                        break;
+                    }
+                    Err(_) => {
+                        info!("Session {} timed out", session_id);
                         break;
                     }
                 }
@@ -69,18 +167,187 @@ impl Session {
                 if let Command::Quit = command {
                     break;
                 }
-                sender
-                    .send((
-                        session_id.clone(),
-                        command,
-                    ))
-                    .expect("Failed to send command");
+                if sender
+                    .send(LobbyMessage::Command(session_id.clone(), command))
+                    .is_err()
+                {
+                    warn!("Lobby is gone, closing session {}", session_id);
+                    break;
+                }
             }
 
-            sender
-                .send((session_id.clone(), Command::Quit))
-                .expect("Cannot send disconnect command");
+            cancel.cancel();
+            if sender
+                .send(LobbyMessage::Command(session_id.clone(), Command::Quit))
+                .is_err()
+            {
+                warn!("Cannot send disconnect command for session {}", session_id);
+            }
             info!("End handle connection - connection closed");
         });
     }
 }
+
+impl SessionHandle {
+    pub fn send_response(&self, message: Response) {
+        if self.cancel.is_cancelled() {
+            return;
+        }
+        if let Response::Error(e) = &message {
+            self.metrics.error(e.name());
+        }
+        match self.outbox.try_send(message) {
+            Ok(_) => {}
+            Err(TrySendError::Full(_)) => {
+                // Cancelling the session stops the reading task, which will then
+                // send the Quit command for this session
+                warn!("Outbox of session {} is full", self.id);
+                self.disconnect();
+            }
+            Err(TrySendError::Closed(_)) => {
+                debug!("Session {} is not writing anymore", self.id);
+            }
+        }
+    }
+
+    pub fn disconnect(&self) {
+        info!("Disconnecting session {}", self.id);
+        self.cancel.cancel();
+    }
+
+    async fn write_responses(
+        session_id: String,
+        mut writer: Writer,
+        mut outbox: Receiver<Response>,
+        cancel: CancellationToken,
+        write_timeout: Duration,
+    ) {
+        // The protocol is in English, and so are its texts until the
+        // client asks otherwise with LANG
+        let mut lang = Lang::En;
+        loop {
+            let message = select! {
+                _ = cancel.cancelled() => break,
+                message = outbox.recv() => message,
+            };
+            let message = match message {
+                Some(message) => message,
+                None => break,
+            };
+            if let Response::Lang(chosen) = message {
+                lang = chosen;
+            }
+            let message = message.render(lang);
+            if !write_response(&session_id, &mut writer, message, write_timeout).await {
+                cancel.cancel();
+                break;
+            }
+        }
+
+        // Whatever was queued before the disconnection still deserves a try,
+        // as long as the client keeps up
+        let drain = async {
+            while let Ok(message) = outbox.try_recv() {
+                if writer
+                    .write_all(message.render(lang).as_bytes())
+                    .await
+                    .is_err()
+                {
+                    break;
+                }
+            }
+        };
+        if timeout(write_timeout, drain).await.is_err() {
+            debug!("Dropping undelivered responses for session {}", session_id);
+        }
+        if let Err(e) = writer.shutdown().await {
+            debug!("Session {} already disconnected: {}", session_id, e);
+        }
+        debug!("End writing to session {}", session_id);
+    }
+}
+
+async fn write_response(
+    session_id: &str,
+    writer: &mut Writer,
+    message: String,
+    write_timeout: Duration,
+) -> bool {
+    match timeout(write_timeout, writer.write_all(message.as_bytes())).await {
+        Ok(Ok(_)) => true,
+        Ok(Err(e)) => {
+            warn!("Failed to write to session {}: {}", session_id, e);
+            false
+        }
+        Err(_) => {
+            warn!("Writing to session {} timed out", session_id);
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::{
+        io::{duplex, DuplexStream},
+        sync::mpsc::{unbounded_channel, UnboundedReceiver},
+    };
+
+    /// A session whose client, the returned end of a pipe that holds a few
+    /// bytes only, never reads what it is sent.
+    fn stalled_session(
+        write_timeout: Duration,
+    ) -> (Session, UnboundedReceiver<LobbyMessage>, DuplexStream) {
+        let (client, server) = duplex(16);
+        let (sender, receiver) = unbounded_channel();
+        let heartbeat = HeartbeatConfig {
+            write_timeout,
+            ..HeartbeatConfig::default()
+        };
+        let session = Session::new(
+            server,
+            "slow".to_string(),
+            sender,
+            &heartbeat,
+            &TaskTracker::new(),
+            Arc::new(Metrics::default()),
+        );
+        (session, receiver, client)
+    }
+
+    async fn expect_quit(receiver: &mut UnboundedReceiver<LobbyMessage>) {
+        let message = timeout(Duration::from_secs(5), receiver.recv())
+            .await
+            .expect("The session was not closed in time");
+        assert!(matches!(
+            message,
+            Some(LobbyMessage::Command(id, Command::Quit)) if id == "slow"
+        ));
+    }
+
+    #[tokio::test]
+    async fn full_outbox_closes_the_session() {
+        // Writes never time out here, only the outbox can stop the session
+        let (mut session, mut receiver, _client) = stalled_session(Duration::from_secs(60));
+        session.read_commands();
+
+        for _ in 0..=OUTBOX_SIZE + 1 {
+            session.send_response(Response::Ping);
+        }
+
+        assert!(session.handle.cancel.is_cancelled());
+        expect_quit(&mut receiver).await;
+    }
+
+    #[tokio::test]
+    async fn stuck_write_closes_the_session() {
+        let (mut session, mut receiver, _client) = stalled_session(Duration::from_millis(100));
+        session.read_commands();
+
+        session.send_response(Response::Motd("A message longer than the pipe".to_string()));
+
+        expect_quit(&mut receiver).await;
+        assert!(session.handle.cancel.is_cancelled());
+    }
+}
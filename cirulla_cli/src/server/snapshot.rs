@@ -0,0 +1,83 @@
+use super::table::DealtSeed;
+use cirulla_lib::Game;
+use serde::{Deserialize, Serialize};
+use std::{fs, io, path::Path};
+
+/// Everything needed to bring a table back after a restart. The seats are
+/// the players of the game: a player takes their seat back by joining the
+/// table again with the same name.
+#[derive(Serialize, Deserialize)]
+pub struct TableSnapshot {
+    pub id: u8,
+    pub name: String,
+    pub player_max: u8,
+    pub owner: String,
+    pub game: Game,
+    /// Seed of the hand being played, still to be revealed
+    #[serde(default)]
+    pub dealt: Option<DealtSeed>,
+}
+
+pub fn save(path: &Path, tables: &[TableSnapshot]) -> io::Result<()> {
+    let content = serde_json::to_string(tables).map_err(io::Error::other)?;
+    // Written aside and then moved, so a crash never leaves half a file
+    let temporary = path.with_extension("tmp");
+    fs::write(&temporary, content)?;
+    fs::rename(&temporary, path)
+}
+
+pub fn load(path: &Path) -> io::Result<Vec<TableSnapshot>> {
+    let content = fs::read_to_string(path)?;
+    serde_json::from_str(&content).map_err(io::Error::other)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    #[test]
+    fn running_game_survives_a_round_trip() {
+        let mut game = Game::new(21);
+        game.add_player("Anna", None).unwrap();
+        game.add_player("Bepi", None).unwrap();
+        game.start_game().unwrap();
+        game.start_hand().unwrap();
+        game.start_round().unwrap();
+        let card = game.current_player().hand[0].to_string();
+        game.player_play(&card).unwrap();
+
+        let path = env::temp_dir().join(format!("cirulla-snapshot-{}.json", std::process::id()));
+        let saved = TableSnapshot {
+            id: 7,
+            name: "Friendly table".to_string(),
+            player_max: 2,
+            owner: "Anna".to_string(),
+            game,
+            dealt: None,
+        };
+        save(&path, &[saved]).unwrap();
+        let restored = load(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(restored.len(), 1);
+        let restored = &restored[0];
+        assert_eq!(restored.id, 7);
+        assert_eq!(restored.owner, "Anna");
+        assert_eq!(restored.game.players[1].name, "Bepi");
+        assert_eq!(
+            restored
+                .game
+                .players
+                .iter()
+                .map(|p| p.hand.len())
+                .sum::<usize>(),
+            5
+        );
+        assert!(restored
+            .game
+            .players
+            .iter()
+            .all(|p| p.hand.iter().all(|c| c.to_string() != card)));
+    }
+}
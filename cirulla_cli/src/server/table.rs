@@ -1,7 +1,24 @@
-use super::response::ServiceError;
-use cirulla_lib::Game;
+use super::admin::AdminReply;
+use super::audit::{AuditEvent, AuditLog};
+use super::lobby::LobbyMessage;
+use super::metrics::Metrics;
+use super::response::{Response, ServiceError};
+use super::session::SessionHandle;
+use super::snapshot::TableSnapshot;
+use cirulla_lib::{Game, GameError, GameEvent, NextAction, Seed};
+use log::{debug, info, warn};
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+use tokio::select;
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
+use tokio::sync::oneshot;
+use tokio::time::sleep_until;
+
+/// How long a restored game waits for its players to take their seats back.
+const RESUME_TIMEOUT: Duration = Duration::from_secs(10 * 60);
 
 #[derive(Clone)]
 pub struct TableInfo {
@@ -10,46 +27,173 @@ pub struct TableInfo {
     pub player_count: u8,
     pub player_max: u8,
     pub win_at: u8,
+    pub started: bool,
+}
+
+/// Commands the lobby forwards to the actor running a table.
+pub enum TableCommand {
+    Join(SessionHandle, String),
+    Leave(String),
+    Play(String, String, Instant),
+    Entropy(String, String),
+    Quit(String),
+    Close,
+    Dump(oneshot::Sender<AdminReply>),
+    Snapshot(oneshot::Sender<TableSnapshot>),
+}
+
+/// Notifications a table actor sends back to the lobby.
+pub enum TableEvent {
+    Updated(TableInfo),
+    Left(u8, String),
+    Closed(u8),
+}
+
+/// The lobby side of a running table: the last known info and the channel
+/// to reach its actor.
+pub struct TableHandle {
+    pub info: TableInfo,
+    pub owner: String,
+    pub announced: bool,
+    sender: UnboundedSender<TableCommand>,
+}
+
+impl TableHandle {
+    pub fn send(&self, command: TableCommand) -> bool {
+        self.sender.send(command).is_ok()
+    }
+}
+
+/// Tells the lobby that a table is gone, whether its actor ended normally
+/// or panicked.
+struct ClosingNotice {
+    table_id: u8,
+    lobby: UnboundedSender<LobbyMessage>,
+}
+
+impl Drop for ClosingNotice {
+    fn drop(&mut self) {
+        if thread::panicking() {
+            warn!("Table {} crashed", self.table_id);
+        }
+        let _ = self
+            .lobby
+            .send(LobbyMessage::Table(TableEvent::Closed(self.table_id)));
+    }
+}
+
+/// What a hand was shuffled from: the seed announced with SEED COMMIT and
+/// the entropy the players mixed into it.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct DealtSeed {
+    pub seed: Seed,
+    pub entropy: Vec<String>,
 }
 
 pub struct Table {
     pub id: u8,
     pub name: String,
     pub player_max: u8,
+    pub owner: String,
     pub game: Game,
     pub sessions_players: HashMap<String, String>,
+    sessions: HashMap<String, SessionHandle>,
+    started: bool,
+    finished: bool,
+    /// Seed of the next hand, already committed to, with the entropy the
+    /// players sent for it by player ID
+    next_seed: Seed,
+    entropy: Vec<(String, String)>,
+    dealt: Option<DealtSeed>,
+    /// Until when a restored game waits for its seats to be taken back
+    resume_by: Option<Instant>,
+    metrics: Arc<Metrics>,
+    audit: AuditLog,
 }
 
 impl Table {
-    pub fn new(name: String, player_max: u8, win_at: u8) -> Table {
-        static TABLE_ID: AtomicU8 = AtomicU8::new(1);
+    pub fn new(
+        id: u8,
+        name: String,
+        owner: String,
+        player_max: u8,
+        win_at: u8,
+        metrics: Arc<Metrics>,
+        audit: AuditLog,
+    ) -> Table {
         Table {
-            id: TABLE_ID.fetch_add(1, Ordering::SeqCst),
+            id,
             name,
             player_max,
+            owner,
             game: Game::new(win_at),
             sessions_players: HashMap::new(),
+            sessions: HashMap::new(),
+            started: false,
+            finished: false,
+            next_seed: Seed::random(),
+            entropy: Vec::new(),
+            dealt: None,
+            resume_by: None,
+            metrics,
+            audit,
+        }
+    }
+
+    /// Brings back a table saved while its game was running; its seats wait
+    /// for their players to join again, for up to [RESUME_TIMEOUT].
+    pub fn restore(snapshot: TableSnapshot, metrics: Arc<Metrics>, audit: AuditLog) -> Table {
+        Table {
+            id: snapshot.id,
+            name: snapshot.name,
+            player_max: snapshot.player_max,
+            owner: snapshot.owner,
+            game: snapshot.game,
+            sessions_players: HashMap::new(),
+            sessions: HashMap::new(),
+            started: true,
+            finished: false,
+            next_seed: Seed::random(),
+            entropy: Vec::new(),
+            dealt: snapshot.dealt,
+            resume_by: Some(Instant::now() + RESUME_TIMEOUT),
+            metrics,
+            audit,
+        }
+    }
+
+    fn snapshot(&mut self) -> TableSnapshot {
+        let win_at = self.game.win_at;
+        TableSnapshot {
+            id: self.id,
+            name: self.name.clone(),
+            player_max: self.player_max,
+            owner: self.owner.clone(),
+            game: std::mem::replace(&mut self.game, Game::new(win_at)),
+            dealt: self.dealt.take(),
         }
     }
 
     pub fn as_info(&self) -> TableInfo {
         TableInfo {
-            id: self.id.clone(),
+            id: self.id,
             name: self.name.clone(),
             player_max: self.player_max,
             player_count: self.game.players.len() as u8,
             win_at: self.game.win_at,
+            started: self.started,
         }
     }
 
     pub fn add_session(
         &mut self,
-        session_id: String,
+        session: SessionHandle,
         player_name: String,
     ) -> Result<(), ServiceError> {
-        match self.game.add_player(&player_name, Some(session_id.clone())) {
+        match self.game.add_player(&player_name, Some(session.id.clone())) {
             Ok(key) => {
-                self.sessions_players.insert(session_id, key);
+                self.sessions_players.insert(session.id.clone(), key);
+                self.sessions.insert(session.id.clone(), session);
                 Ok(())
             }
             Err(e) => Err(ServiceError::GameError(e)),
@@ -58,11 +202,17 @@ impl Table {
 
     pub fn remove_session(&mut self, session_id: &str) -> Result<(), ServiceError> {
         if let Some(player_id) = self.sessions_players.get(session_id) {
-            return match self.game.remove_player(player_id) {
+            // A restored game keeps the seat for whoever takes it back
+            let removed = match self.resume_by {
+                Some(_) => Ok(()),
+                None => self.game.remove_player(player_id),
+            };
+            return match removed {
                 Ok(_) => {
                     self.sessions_players
                         .remove(session_id)
                         .expect("The session has been removed by something unexpected");
+                    self.sessions.remove(session_id);
                     Ok(())
                 }
                 Err(e) => Err(ServiceError::GameError(e)),
@@ -70,4 +220,428 @@ impl Table {
         }
         Ok(())
     }
+
+    /// Moves the table on its own task; from now on it can only be reached
+    /// through the returned handle.
+    pub fn spawn(self, lobby: UnboundedSender<LobbyMessage>) -> TableHandle {
+        let (sender, receiver) = unbounded_channel::<TableCommand>();
+        let info = self.as_info();
+        let owner = self.owner.clone();
+
+        tokio::spawn(async move {
+            let _notice = ClosingNotice {
+                table_id: self.id,
+                lobby: lobby.clone(),
+            };
+            self.run(receiver, lobby).await;
+        });
+
+        TableHandle {
+            info,
+            owner,
+            announced: false,
+            sender,
+        }
+    }
+
+    async fn run(
+        mut self,
+        mut commands: UnboundedReceiver<TableCommand>,
+        lobby: UnboundedSender<LobbyMessage>,
+    ) {
+        info!("Table {} is open", self.id);
+        loop {
+            let command = match self.resume_by {
+                Some(deadline) => select! {
+                    command = commands.recv() => command,
+                    _ = sleep_until(deadline.into()) => {
+                        info!("Table {} was not resumed in time", self.id);
+                        break;
+                    }
+                },
+                None => commands.recv().await,
+            };
+            let command = match command {
+                Some(command) => command,
+                None => break,
+            };
+            match command {
+                TableCommand::Join(session, player_name) => {
+                    self.join(session, player_name, &lobby);
+                }
+                TableCommand::Leave(session_id) => {
+                    self.leave(&session_id, &lobby);
+                }
+                TableCommand::Play(session_id, card, received) => {
+                    if self.play(&session_id, card) {
+                        self.metrics.move_played(received.elapsed());
+                    }
+                }
+                TableCommand::Entropy(session_id, entropy) => {
+                    self.add_entropy(&session_id, entropy);
+                }
+                TableCommand::Quit(session_id) if self.resume_by.is_some() => {
+                    debug!(
+                        "Session {} gave up its seat at table {}",
+                        session_id, self.id
+                    );
+                    self.leave(&session_id, &lobby);
+                }
+                TableCommand::Quit(session_id) => {
+                    debug!("Session {} quit table {}", session_id, self.id);
+                    break;
+                }
+                TableCommand::Close => {
+                    info!("Table {} closed by an admin", self.id);
+                    break;
+                }
+                TableCommand::Dump(reply) => {
+                    let dump = serde_json::to_string_pretty(&self.game).expect("Should serialize");
+                    let _ = reply.send(Ok(format!("{}\n", dump)));
+                }
+                TableCommand::Snapshot(reply) => {
+                    info!("Table {} saved", self.id);
+                    let _ = reply.send(self.snapshot());
+                    break;
+                }
+            }
+
+            // A running game waits for its players even when nobody is
+            // seated, as it happens after a restore
+            if self.finished || (self.sessions_players.is_empty() && !self.started) {
+                break;
+            }
+        }
+        info!("Table {} is closed", self.id);
+        self.audit.record(AuditEvent::TableClosed {
+            table: self.id,
+            finished: self.finished,
+        });
+    }
+
+    fn notify(&self, lobby: &UnboundedSender<LobbyMessage>, event: TableEvent) {
+        if lobby.send(LobbyMessage::Table(event)).is_err() {
+            warn!("Table {} cannot reach the lobby", self.id);
+        }
+    }
+
+    fn send(&self, session_id: &str, message: Response) {
+        match self.sessions.get(session_id) {
+            Some(session) => session.send_response(message),
+            None => warn!("Session {} is not at table {}", session_id, self.id),
+        }
+    }
+
+    fn send_all(&self, message: Response) {
+        for session in self.sessions.values() {
+            session.send_response(message.clone());
+        }
+    }
+
+    fn send_status(&self) {
+        let active_player = self.game.current_player().id.clone();
+        for (session_id, player_id) in self.sessions_players.iter() {
+            self.send(
+                session_id,
+                Response::GameStatus(self.game.as_game_for_player(player_id)),
+            );
+            if player_id == &active_player {
+                self.send(session_id, Response::Play);
+            } else {
+                self.send(session_id, Response::Wait);
+            }
+        }
+    }
+
+    fn join(
+        &mut self,
+        session: SessionHandle,
+        player_name: String,
+        lobby: &UnboundedSender<LobbyMessage>,
+    ) {
+        if self.started {
+            self.take_seat(session, player_name, lobby);
+            return;
+        }
+
+        match self.add_session(session.clone(), player_name.clone()) {
+            Ok(_) => {
+                self.audit.record(AuditEvent::TableJoined {
+                    table: self.id,
+                    session: session.id.clone(),
+                    name: player_name,
+                });
+                session.send_response(Response::TableJoined(self.id));
+                session.send_response(Response::SeedCommit(self.next_seed.commitment()));
+                session.send_response(Response::Wait);
+                self.maybe_start_game();
+                self.notify(lobby, TableEvent::Updated(self.as_info()));
+            }
+            Err(e) => {
+                session.send_response(Response::Error(e));
+                self.notify(lobby, TableEvent::Left(self.id, session.id));
+            }
+        }
+    }
+
+    /// Gives a seat of a restored game back to the player with the same name,
+    /// resuming the game once every seat is taken.
+    fn take_seat(
+        &mut self,
+        session: SessionHandle,
+        player_name: String,
+        lobby: &UnboundedSender<LobbyMessage>,
+    ) {
+        let free_seat = self
+            .game
+            .players
+            .iter()
+            .find(|p| p.name == player_name)
+            .map(|p| p.id.clone())
+            .filter(|player_id| !self.sessions_players.values().any(|id| id == player_id));
+        let player_id = match free_seat {
+            Some(player_id) => player_id,
+            None => {
+                session.send_response(Response::Error(ServiceError::GameError(
+                    GameError::GameAlreadyStarted,
+                )));
+                self.notify(lobby, TableEvent::Left(self.id, session.id));
+                return;
+            }
+        };
+
+        self.audit.record(AuditEvent::TableJoined {
+            table: self.id,
+            session: session.id.clone(),
+            name: player_name,
+        });
+        self.sessions_players.insert(session.id.clone(), player_id);
+        self.sessions.insert(session.id.clone(), session.clone());
+        session.send_response(Response::TableJoined(self.id));
+        session.send_response(Response::SeedCommit(self.next_seed.commitment()));
+        session.send_response(Response::Wait);
+        self.notify(lobby, TableEvent::Updated(self.as_info()));
+
+        if self.sessions_players.len() == self.game.players.len() {
+            info!("Table {} resumes its game", self.id);
+            self.resume_by = None;
+            self.send_all(Response::GameStart(self.id));
+            self.send_status();
+        }
+    }
+
+    fn leave(&mut self, session_id: &str, lobby: &UnboundedSender<LobbyMessage>) {
+        let session = self.sessions.get(session_id).cloned();
+        match self.remove_session(session_id) {
+            Ok(_) => {
+                self.audit.record(AuditEvent::TableLeft {
+                    table: self.id,
+                    session: session_id.to_string(),
+                });
+                if let Some(session) = session {
+                    session.send_response(Response::TableLeaved(self.id));
+                }
+                self.notify(lobby, TableEvent::Left(self.id, session_id.to_string()));
+                self.notify(lobby, TableEvent::Updated(self.as_info()));
+            }
+            Err(e) => {
+                self.send(session_id, Response::Error(e));
+            }
+        }
+    }
+
+    fn maybe_start_game(&mut self) {
+        if (self.sessions_players.len() as u8) != self.player_max {
+            return;
+        }
+
+        if let Err(e) = self
+            .game
+            .start_game()
+            .and_then(|_| self.start_hand())
+            .and_then(|_| self.game.start_round())
+        {
+            self.send_all(Response::Error(ServiceError::GameError(e)));
+            return;
+        }
+
+        self.started = true;
+        self.metrics.game_started();
+        self.audit.record(AuditEvent::GameStarted {
+            table: self.id,
+            players: self.game.players.iter().map(|p| p.name.clone()).collect(),
+        });
+        self.audit_deal();
+        self.send_all(Response::GameStart(self.id));
+        self.send_status();
+        self.commit_next_hand();
+    }
+
+    /// Deals a hand from the seed committed to earlier, mixed with the
+    /// entropy the players sent since then.
+    fn start_hand(&mut self) -> Result<(), GameError> {
+        let entropy: Vec<String> = self.entropy.drain(..).map(|(_, e)| e).collect();
+        self.game
+            .start_hand_with_seed(&self.next_seed.mix(&entropy))?;
+        self.dealt = Some(DealtSeed {
+            seed: self.next_seed,
+            entropy,
+        });
+        self.next_seed = Seed::random();
+        Ok(())
+    }
+
+    /// Announces the seed of the next hand, so that the players can send
+    /// their entropy while the current one is played.
+    fn commit_next_hand(&self) {
+        self.send_all(Response::SeedCommit(self.next_seed.commitment()));
+    }
+
+    fn add_entropy(&mut self, session_id: &str, entropy: String) {
+        let player_id = match self.sessions_players.get(session_id) {
+            Some(player_id) => player_id.clone(),
+            None => {
+                warn!("Session {} is not at table {}", session_id, self.id);
+                return;
+            }
+        };
+        // A player contributes once per hand: the last value counts
+        self.entropy.retain(|(id, _)| *id != player_id);
+        self.entropy.push((player_id, entropy));
+    }
+
+    fn audit_deal(&self) {
+        self.audit.record(AuditEvent::Dealt {
+            table: self.id,
+            hands: self
+                .game
+                .players
+                .iter()
+                .map(|p| (p.name.clone(), p.hand.clone()))
+                .collect(),
+            cards_on_table: self.game.table.clone(),
+            cards_in_deck: self.game.deck.len(),
+        });
+    }
+
+    fn reject_play(&self, session_id: &str, player_id: &str, card: String, error: ServiceError) {
+        self.audit.record(AuditEvent::PlayRejected {
+            table: self.id,
+            session: session_id.to_string(),
+            player: self.player_name(player_id),
+            card,
+            error: error.to_string(),
+        });
+        self.send(session_id, Response::Error(error));
+    }
+
+    fn player_name(&self, player_id: &str) -> String {
+        self.game
+            .players
+            .iter()
+            .find(|p| p.id == player_id)
+            .map(|p| p.name.clone())
+            .unwrap_or_default()
+    }
+
+    /// Plays `card` for the player of `session_id`, giving back whether it
+    /// was played or rejected.
+    fn play(&mut self, session_id: &str, card: String) -> bool {
+        let player_id = match self.sessions_players.get(session_id) {
+            Some(player_id) => player_id.clone(),
+            None => {
+                warn!("Session {} is not at table {}", session_id, self.id);
+                return false;
+            }
+        };
+
+        if self.sessions_players.len() != self.game.players.len() {
+            self.reject_play(
+                session_id,
+                &player_id,
+                card,
+                ServiceError::WaitingForPlayers,
+            );
+            return false;
+        }
+
+        if player_id != self.game.current_player().id {
+            self.reject_play(session_id, &player_id, card, ServiceError::NotYourTurn);
+            return false;
+        }
+
+        let hand = self.game.current_player().hand.clone();
+        let cards_on_table = self.game.table.clone();
+        if let Err(e) = self.game.player_play(&card) {
+            self.reject_play(session_id, &player_id, card, ServiceError::GameError(e));
+            return false;
+        }
+        let captured = self
+            .game
+            .take_events()
+            .into_iter()
+            .find_map(|event| match event {
+                GameEvent::Played { taken, .. } => Some(taken),
+                _ => None,
+            })
+            .unwrap_or_default();
+        self.audit.record(AuditEvent::Play {
+            table: self.id,
+            session: session_id.to_string(),
+            player: self.player_name(&player_id),
+            card,
+            hand,
+            captured,
+            cards_on_table,
+        });
+
+        let next_action = self.game.next_round_action();
+        debug!("Next action: {:?}", next_action);
+
+        match next_action {
+            NextAction::NextPlayer => {}
+            NextAction::NextRound => {
+                self.game.start_round().unwrap();
+                self.audit_deal();
+            }
+            NextAction::EndHand => {
+                let result = self.game.end_hand().unwrap();
+                let someone_wins = result.someone_wins;
+                self.audit.record(AuditEvent::HandResult {
+                    table: self.id,
+                    result: result.clone(),
+                });
+                self.send_all(Response::HandResult(result));
+                if let Some(dealt) = self.dealt.take() {
+                    self.send_all(Response::SeedReveal(dealt));
+                }
+
+                if someone_wins {
+                    self.send_all(Response::GameEnd);
+                    self.finished = true;
+                    self.metrics.game_finished();
+                    self.audit.record(AuditEvent::GameEnded {
+                        table: self.id,
+                        points: self
+                            .game
+                            .players
+                            .iter()
+                            .map(|p| (p.name.clone(), p.points))
+                            .collect(),
+                    });
+                    return true;
+                }
+
+                self.start_hand().unwrap();
+                self.game.start_round().unwrap();
+                self.audit_deal();
+                self.send_status();
+                self.commit_next_hand();
+                return true;
+            }
+        }
+
+        self.send_status();
+        true
+    }
 }
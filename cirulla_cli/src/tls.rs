@@ -0,0 +1,193 @@
+use std::{io::Error, path::Path, sync::Arc};
+use tokio_rustls::{
+    rustls::{
+        client::{
+            danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier},
+            WebPkiServerVerifier,
+        },
+        crypto::ring::default_provider,
+        pki_types::{pem::PemObject, CertificateDer, PrivateKeyDer, ServerName, UnixTime},
+        ClientConfig, DigitallySignedStruct, RootCertStore, ServerConfig, SignatureScheme,
+    },
+    TlsAcceptor, TlsConnector,
+};
+
+/// Builds the acceptor used by the server from a PEM certificate chain and
+/// its PEM private key.
+pub fn server_acceptor(cert: &Path, key: &Path) -> Result<TlsAcceptor, Error> {
+    let certs = CertificateDer::pem_file_iter(cert)
+        .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+        .map_err(|e| Error::other(format!("invalid certificate {}: {}", cert.display(), e)))?;
+    let key = PrivateKeyDer::from_pem_file(key)
+        .map_err(|e| Error::other(format!("invalid key {}: {}", key.display(), e)))?;
+
+    let config = ServerConfig::builder_with_provider(Arc::new(default_provider()))
+        .with_safe_default_protocol_versions()
+        .map_err(Error::other)?
+        .with_no_client_auth()
+        .with_single_cert(certs, key)
+        .map_err(Error::other)?;
+
+    Ok(TlsAcceptor::from(Arc::new(config)))
+}
+
+/// Builds the connector used by the client. The well known root
+/// certificates are always trusted; the certificates found in `trusted` are
+/// accepted both as authorities and as the exact certificate presented by
+/// the server, which is how a self-signed server gets accepted.
+pub fn client_connector(trusted: Option<&Path>) -> Result<TlsConnector, Error> {
+    let mut roots = RootCertStore::empty();
+    roots.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned());
+
+    let mut pinned = Vec::new();
+    if let Some(path) = trusted {
+        for cert in CertificateDer::pem_file_iter(path)
+            .map_err(|e| Error::other(format!("invalid certificate {}: {}", path.display(), e)))?
+        {
+            let cert = cert.map_err(|e| {
+                Error::other(format!("invalid certificate {}: {}", path.display(), e))
+            })?;
+            // A self-signed leaf is not a valid authority: it will be pinned anyway
+            let _ = roots.add(cert.clone());
+            pinned.push(cert);
+        }
+    }
+
+    let provider = Arc::new(default_provider());
+    let verifier = PinnedVerifier {
+        pinned,
+        webpki: WebPkiServerVerifier::builder_with_provider(Arc::new(roots), provider.clone())
+            .build()
+            .map_err(Error::other)?,
+    };
+
+    let config = ClientConfig::builder_with_provider(provider)
+        .with_safe_default_protocol_versions()
+        .map_err(Error::other)?
+        .dangerous()
+        .with_custom_certificate_verifier(Arc::new(verifier))
+        .with_no_client_auth();
+
+    Ok(TlsConnector::from(Arc::new(config)))
+}
+
+/// Accepts the server certificates explicitly trusted by the user, and
+/// verifies every other one against the root certificates.
+#[derive(Debug)]
+struct PinnedVerifier {
+    pinned: Vec<CertificateDer<'static>>,
+    webpki: Arc<WebPkiServerVerifier>,
+}
+
+impl ServerCertVerifier for PinnedVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        intermediates: &[CertificateDer<'_>],
+        server_name: &ServerName<'_>,
+        ocsp_response: &[u8],
+        now: UnixTime,
+    ) -> Result<ServerCertVerified, tokio_rustls::rustls::Error> {
+        if self.pinned.iter().any(|cert| cert == end_entity) {
+            return Ok(ServerCertVerified::assertion());
+        }
+        self.webpki
+            .verify_server_cert(end_entity, intermediates, server_name, ocsp_response, now)
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, tokio_rustls::rustls::Error> {
+        self.webpki.verify_tls12_signature(message, cert, dss)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, tokio_rustls::rustls::Error> {
+        self.webpki.verify_tls13_signature(message, cert, dss)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.webpki.supported_verify_schemes()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{env, fs, path::PathBuf};
+    use tokio::io::{duplex, AsyncReadExt, AsyncWriteExt};
+
+    fn write_certificate(name: &str) -> (PathBuf, PathBuf) {
+        let generated = rcgen::generate_simple_self_signed(vec!["localhost".to_string()]).unwrap();
+        let dir = env::temp_dir().join(format!("cirulla-tls-{}-{}", name, std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let cert = dir.join("cert.pem");
+        let key = dir.join("key.pem");
+        fs::write(&cert, generated.cert.pem()).unwrap();
+        fs::write(&key, generated.key_pair.serialize_pem()).unwrap();
+        (cert, key)
+    }
+
+    #[tokio::test]
+    async fn self_signed_certificate_is_trusted_when_given() {
+        let (cert, key) = write_certificate("trusted");
+        let acceptor = server_acceptor(&cert, &key).unwrap();
+        let connector = client_connector(Some(&cert)).unwrap();
+
+        let (client_side, server_side) = duplex(4096);
+        let server = tokio::spawn(async move {
+            let mut stream = acceptor.accept(server_side).await.unwrap();
+            stream.write_all(b"HI Anna\n").await.unwrap();
+            stream.shutdown().await.unwrap();
+        });
+
+        let name = ServerName::try_from("localhost").unwrap();
+        let mut stream = connector.connect(name, client_side).await.unwrap();
+        let mut received = String::new();
+        stream.read_to_string(&mut received).await.unwrap();
+        server.await.unwrap();
+
+        assert_eq!(received, "HI Anna\n");
+    }
+
+    #[tokio::test]
+    async fn self_signed_certificate_is_refused_by_default() {
+        let (cert, key) = write_certificate("refused");
+        let acceptor = server_acceptor(&cert, &key).unwrap();
+        let connector = client_connector(None).unwrap();
+
+        let (client_side, server_side) = duplex(4096);
+        tokio::spawn(async move { acceptor.accept(server_side).await });
+
+        let name = ServerName::try_from("localhost").unwrap();
+        assert!(connector.connect(name, client_side).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn another_self_signed_certificate_is_refused() {
+        let (cert, key) = write_certificate("server");
+        let (other_cert, _) = write_certificate("other");
+        let acceptor = server_acceptor(&cert, &key).unwrap();
+        let connector = client_connector(Some(&other_cert)).unwrap();
+
+        let (client_side, server_side) = duplex(4096);
+        tokio::spawn(async move { acceptor.accept(server_side).await });
+
+        let name = ServerName::try_from("localhost").unwrap();
+        assert!(connector.connect(name, client_side).await.is_err());
+    }
+
+    #[test]
+    fn missing_files_are_reported() {
+        let missing = env::temp_dir().join("cirulla-tls-missing.pem");
+        assert!(server_acceptor(&missing, &missing).is_err());
+        assert!(client_connector(Some(&missing)).is_err());
+    }
+}
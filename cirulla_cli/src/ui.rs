@@ -1,22 +1,117 @@
-use cirulla_lib::{Card, Effect, Game, HandResult, Player};
+mod layout;
+mod plain;
+mod theme;
+
+use cirulla_cli::i18n::{Lang, Texts};
+use cirulla_lib::{Card, Game, GameEvent, HandResult, Player};
 use crossterm::style::Stylize;
 use crossterm::{
     cursor::{Hide, MoveTo, Show},
-    event::{read, Event, KeyCode},
-    style::{Color, Print, SetForegroundColor},
-    terminal::{disable_raw_mode, enable_raw_mode, Clear, ClearType},
+    event::{
+        read, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, MouseButton, MouseEventKind,
+    },
+    style::Print,
+    terminal::{self, disable_raw_mode, enable_raw_mode, Clear, ClearType},
     QueueableCommand,
 };
+use layout::{Layout, Rect};
+pub use plain::PlainUI;
 use std::collections::HashMap;
 use std::{
     io::{stdout, Error, Stdout, Write},
-    process,
+    process, thread,
+    time::Duration,
 };
+pub use theme::{Palette, Suits, Theme};
+
+/// How long the cards just taken stay highlighted on the table.
+const CAPTURE_DELAY: Duration = Duration::from_millis(700);
+
+/// What the local game needs from whoever shows it to the players: the
+/// full screen [UI] or the line by line [PlainUI].
+pub trait Frontend {
+    /// Draws the whole table, showing the hands marked in `face_up` and
+    /// those a player had to reveal.
+    fn draw_table(&mut self, game: &Game, face_up: &[bool]) -> Result<(), Error>;
+
+    /// Waits for `name` to sit in front of the screen, hiding every hand
+    /// meanwhile so that nobody sees the cards of someone else.
+    fn hand_over(&mut self, game: &Game, name: &str) -> Result<(), Error>;
+
+    /// Tells the players what happened since the last time.
+    fn show_events(&mut self, game: &Game, events: &[GameEvent]) -> Result<(), Error>;
+
+    /// Asks the current player for the card to play and, when it can take
+    /// different sets of cards from the table, for the one to take.
+    fn ask_for_card(&mut self, game: &Game) -> Result<(String, Vec<Card>), Error>;
+
+    fn show_hand_result(&mut self, result: &HandResult, players: &[Player]) -> Result<(), Error>;
+
+    fn draw_winner(&mut self, game: &Game) -> Result<(), Error>;
+}
+
+/// `event` told in words, as it goes in the game log.
+fn describe(texts: &Texts, theme: &Theme, game: &Game, event: &GameEvent) -> String {
+    let name = |player_id: &str| {
+        game.players
+            .iter()
+            .find(|p| p.id == player_id)
+            .map(|p| p.name.clone())
+            .unwrap_or_default()
+    };
+
+    let entry = match event {
+        GameEvent::HandStarted { dealer } => (texts.new_hand)(&name(dealer)),
+        GameEvent::Effect { player_id, effect } => {
+            format!("{}: {}", name(player_id), (texts.effect)(effect))
+        }
+        GameEvent::Played {
+            player_id,
+            card,
+            taken,
+            broom,
+        } => {
+            let mut entry = (texts.plays)(&name(player_id), &theme.label(card));
+            if !taken.is_empty() {
+                entry.push_str(&(texts.takes)(&theme.labels(taken)));
+            }
+            if *broom {
+                entry.push_str(texts.broom);
+            }
+            entry
+        }
+        GameEvent::LastTake { player_id, cards } => {
+            (texts.last_take)(&name(player_id), &theme.labels(cards))
+        }
+    };
+    theme.text(&entry)
+}
 
-const PLAYER_HEIGHT: u16 = 11;
+/// What the player did while the UI was waiting.
+enum Input {
+    Key(KeyCode),
+    /// A left click on a column and a row
+    Click(u16, u16),
+    /// The terminal changed size: whatever is on screen must be drawn again
+    Resized,
+}
 
 pub struct UI {
     stdout: Stdout,
+    texts: &'static Texts,
+    theme: Theme,
+    columns: u16,
+    rows: u16,
+    /// What happened so far, oldest first
+    log: Vec<String>,
+    /// The cards on the table as last drawn
+    drawn_table: Vec<Card>,
+    /// The hands shown face up when the table was last drawn
+    face_up: Vec<bool>,
+    /// The box to click instead of pressing the key it asks for
+    continue_box: Option<Rect>,
+    /// The rules of the game last drawn, as the help tells them
+    rules: Vec<String>,
 }
 
 impl Drop for UI {
@@ -26,204 +121,471 @@ impl Drop for UI {
 }
 
 impl UI {
-    pub fn new() -> UI {
+    pub fn new(lang: Lang, theme: Theme) -> UI {
         let mut stdout = stdout();
         enable_raw_mode().unwrap();
-        stdout.queue(Hide).unwrap().flush().unwrap();
-
-        UI { stdout }
+        stdout
+            .queue(Hide)
+            .unwrap()
+            .queue(EnableMouseCapture)
+            .unwrap()
+            .flush()
+            .unwrap();
+        let (columns, rows) = terminal::size().unwrap_or((80, 24));
+
+        UI {
+            stdout,
+            texts: lang.texts(),
+            theme,
+            columns,
+            rows,
+            log: Vec::new(),
+            drawn_table: Vec::new(),
+            face_up: Vec::new(),
+            continue_box: None,
+            rules: Vec::new(),
+        }
     }
 
     pub fn reset(&mut self, clear: bool) {
         disable_raw_mode().unwrap();
-        self.stdout.queue(Show).unwrap();
+        self.stdout
+            .queue(Show)
+            .unwrap()
+            .queue(DisableMouseCapture)
+            .unwrap();
         if clear {
             self.clear().unwrap();
         }
         self.stdout.flush().unwrap();
     }
 
-    pub fn show_hand_result(
+    fn quit(&mut self) -> ! {
+        self.reset(true);
+        process::exit(0);
+    }
+
+    fn layout(&self, players: usize) -> Option<Layout> {
+        Layout::new(self.columns, self.rows, players)
+    }
+
+    /// Waits for a key. While the terminal is too small for `players` it
+    /// just says so, giving back [Input::Resized] once it is big enough.
+    fn next_input(&mut self, players: usize) -> Result<Input, Error> {
+        loop {
+            let fits = self.layout(players).is_some();
+            if !fits {
+                self.too_small(players)?;
+            }
+            match read()? {
+                Event::Key(evt) if fits && evt.code == KeyCode::Char('?') => {
+                    self.help()?;
+                    return Ok(Input::Resized);
+                }
+                Event::Key(evt) if fits => return Ok(Input::Key(evt.code)),
+                Event::Mouse(evt)
+                    if fits && evt.kind == MouseEventKind::Down(MouseButton::Left) =>
+                {
+                    return Ok(Input::Click(evt.column, evt.row));
+                }
+                Event::Key(evt) if evt.code == KeyCode::Char('q') => self.quit(),
+                Event::Resize(columns, rows) => {
+                    self.columns = columns;
+                    self.rows = rows;
+                    if self.layout(players).is_some() {
+                        return Ok(Input::Resized);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Shows the controls and the rules over the game until a key is
+    /// pressed; the screen must then be drawn again.
+    fn help(&mut self) -> Result<(), Error> {
+        let width = self.columns.saturating_sub(2).min(80);
+        let room = width as usize - 3;
+        let controls = self.texts.controls.iter().map(|c| c.to_string()).collect();
+        let sections = [
+            (self.texts.controls_title, controls),
+            (self.texts.rules_title, self.rules.clone()),
+        ];
+
+        let mut lines: Vec<(String, bool)> = Vec::new();
+        for (title, entries) in sections {
+            lines.push((self.theme.text(title), true));
+            for entry in entries {
+                let wrapped = UI::wrap(&self.theme.text(&entry), room - 2);
+                for (i, line) in wrapped.into_iter().enumerate() {
+                    let indent = if i == 0 { "- " } else { "  " };
+                    lines.push((format!("{}{}", indent, line), false));
+                }
+            }
+            lines.push((String::new(), false));
+        }
+        lines.push((self.theme.text(self.texts.help_close), false));
+
+        let height = (lines.len() as u16 + 1).min(self.rows.saturating_sub(1));
+        self.draw_box(0, 0, width, height, true)?;
+        for (row, (line, title)) in lines.iter().take(height as usize - 1).enumerate() {
+            let line = format!(" {:room$}", UI::fit(line, room));
+            self.stdout.queue(MoveTo(1, row as u16 + 1))?;
+            if *title {
+                self.stdout.queue(Print(line.bold()))?;
+            } else {
+                self.stdout.queue(Print(line))?;
+            }
+        }
+        self.apply()?;
+
+        loop {
+            match read()? {
+                Event::Key(_) => return Ok(()),
+                Event::Mouse(evt) if evt.kind == MouseEventKind::Down(MouseButton::Left) => {
+                    return Ok(())
+                }
+                Event::Resize(columns, rows) => {
+                    self.columns = columns;
+                    self.rows = rows;
+                    return Ok(());
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn too_small(&mut self, players: usize) -> Result<(), Error> {
+        let (columns, rows) = Layout::minimum(players);
+        self.clear()?;
+        self.stdout
+            .queue(Print(self.theme.text(self.texts.too_small).bold()))?
+            .queue(MoveTo(0, 1))?
+            .queue(Print(self.theme.text(&(self.texts.needed_size)(
+                columns,
+                rows,
+                self.columns,
+                self.rows,
+            ))))?
+            .queue(MoveTo(0, 2))?
+            .queue(Print(self.theme.text(self.texts.widen_or_quit)))?;
+        self.apply()
+    }
+
+    fn draw_hand_result(
         &mut self,
         result: &HandResult,
-        players_list: &Vec<Player>,
+        players_list: &[Player],
     ) -> Result<(), Error> {
+        let players = players_list.len() as u16;
+        let layout = match self.layout(players_list.len()) {
+            Some(layout) => layout,
+            None => return self.too_small(players_list.len()),
+        };
         self.clear()?;
 
         let mut people = HashMap::new();
         for p in players_list.iter() {
             people.insert(p.id.to_owned(), p.name.to_owned());
         }
+        let name = |id: &Option<String>| match id {
+            Some(id) => people[id].as_str(),
+            None => "-",
+        };
+
+        if layout.columns <= 90 || layout.rows < players * 6 + 13 {
+            return self.draw_compact_hand_result(&layout, result, &people);
+        }
 
+        let titles = [
+            (2, self.texts.cards),
+            (17, self.texts.diamonds),
+            (32, self.texts.primiera),
+            (47, self.texts.pretty_seven),
+            (64, self.texts.high_ladder),
+            (79, self.texts.low_ladder),
+        ];
+        for (column, title) in titles {
+            self.stdout
+                .queue(MoveTo(column, 2))?
+                .queue(Print(self.theme.text(title).bold()))?;
+        }
         self.stdout
-            .queue(MoveTo(2, 2))?
-            .queue(Print(
-                "Carte:         Denari:        Primiera:      Settebello:      Alta:          Bassa:"
-                    .bold(),
-            ))?
             .queue(MoveTo(2, 3))?
-            .queue(Print(
-                match result.cards {
-                    Some(ref cards) => &people[cards],
-                    None => "-",
-                }))?
+            .queue(Print(name(&result.cards)))?
             .queue(MoveTo(17, 3))?
-            .queue(Print(
-                match result.diamonds {
-                    Some(ref diamond) => &people[diamond],
-                    None => "-",
-                }))?
+            .queue(Print(name(&result.diamonds)))?
             .queue(MoveTo(32, 3))?
-            .queue(Print(
-                match result.primiera {
-                    Some(ref primiera) => &people[primiera],
-                    None => "-",
-                }))?
+            .queue(Print(name(&result.primiera)))?
             .queue(MoveTo(47, 3))?
             .queue(Print(&people[&result.pretty_seven]))?
             .queue(MoveTo(64, 3))?
-            .queue(Print(
-                match result.high_ladder {
-                    Some(ref hilad) => &people[hilad],
-                    None => "-",
-                }))?
-                .queue(MoveTo(79, 3))?
-                .queue(Print(match result.low_ladder {
-                    Some(ref low_ladder) => &people[low_ladder],
-                    None => "-",
-                }))?;
+            .queue(Print(name(&result.high_ladder)))?
+            .queue(MoveTo(79, 3))?
+            .queue(Print(name(&result.low_ladder)))?;
 
         if result.low_ladder_value > 0 {
             self.stdout
-                .queue(MoveTo(86, 2))?
+                .queue(MoveTo(80 + self.texts.low_ladder.chars().count() as u16, 2))?
                 .queue(Print(result.low_ladder_value.to_string()))?;
         }
 
+        // The box takes the whole terminal and the cards overlap, as many
+        // as fit inside it: each is 5 columns wide, one every 3 from the 2nd
+        let width = layout.columns - 1;
+        let shown = (width as usize - 4) / 3;
         for (i, a) in result.points.iter().enumerate() {
             self.stdout
                 .queue(MoveTo(2, 7 + (i as u16) * 6))?
                 .queue(Print(&people[&a.player_id]))?;
-            for (j, c) in a.cards_taken.iter().enumerate() {
+            for (j, c) in a.cards_taken.iter().take(shown).enumerate() {
                 self.card(c, 2 + (j as u16) * 3, 8 + (i as u16) * 6, true)?;
             }
         }
 
-        self.draw_box(0, 0, 90, result.points.len() as u16 * 6 + 8, true)?;
-
-        self.wait_for_key('c', 40, result.points.len() as u16 * 6 + 10);
-        Ok(())
+        self.draw_box(0, 0, width, players * 6 + 8, true)?;
+        self.key_box('c', 40, players * 6 + 10)
     }
 
-    pub fn draw_winner(&mut self, game: &Game) -> Result<(), Error> {
-        self.clear()?;
-        self.apply()?;
-
-        let mut points: Vec<(String, u8)> = game
-            .players
-            .iter()
-            .map(|p| (p.name.to_owned(), p.points))
-            .collect();
-        points.sort_unstable_by(|a, b| b.1.cmp(&a.1));
-
-        let last_line: u16 = game.players.len() as u16 + 7;
-        self.draw_box(0, 0, 28, last_line, true)?;
-        self.stdout
-            .queue(MoveTo(5, 2))?
-            .queue(Print("CLASSIFICA FINALE".bold()))?;
+    /// The hand results one under the other, with the cards as labels.
+    fn draw_compact_hand_result(
+        &mut self,
+        layout: &Layout,
+        result: &HandResult,
+        people: &HashMap<String, String>,
+    ) -> Result<(), Error> {
+        let width = layout.columns.min(80) - 1;
+        let name = |id: &Option<String>| match id {
+            Some(id) => people[id].clone(),
+            None => "-".to_string(),
+        };
+        let low_ladder = match result.low_ladder_value {
+            0 => name(&result.low_ladder),
+            value => format!("{} ({})", name(&result.low_ladder), value),
+        };
+        let lines = [
+            (self.texts.cards, name(&result.cards)),
+            (self.texts.diamonds, name(&result.diamonds)),
+            (self.texts.primiera, name(&result.primiera)),
+            (
+                self.texts.pretty_seven,
+                people[&result.pretty_seven].clone(),
+            ),
+            (self.texts.high_ladder, name(&result.high_ladder)),
+            (self.texts.low_ladder, low_ladder),
+        ];
+        for (row, (title, who)) in lines.iter().enumerate() {
+            self.stdout
+                .queue(MoveTo(2, row as u16 + 1))?
+                .queue(Print(self.theme.text(title).bold()))?
+                .queue(MoveTo(15, row as u16 + 1))?
+                .queue(Print(UI::fit(who, width as usize - 16)))?;
+        }
 
-        for (i, (name, points)) in points.iter().enumerate() {
+        for (i, points) in result.points.iter().enumerate() {
+            let row = 8 + i as u16;
+            let label = format!("{}: ", people[&points.player_id]);
+            let room = (width as usize - 3).saturating_sub(label.chars().count());
             self.stdout
-                .queue(MoveTo(3, i as u16 + 4))?
-                .queue(Print(format!("{}° {} - {}", i + 1, name, points)))?;
+                .queue(MoveTo(2, row))?
+                .queue(Print(UI::fit(&label, width as usize - 3).bold()))?;
+            let mut column = 2 + label.chars().count() as u16;
+            for card in points.cards_taken.iter().take(room / 3) {
+                self.card_label(card, column, row, true, false)?;
+                column += 3;
+            }
         }
 
-        self.wait_for_key('q', 40, 22);
-        self.reset(true);
-        process::exit(0);
+        let height = 8 + result.points.len() as u16;
+        self.draw_box(0, 0, width, height, true)?;
+        self.key_box('c', 0, height + 1)
     }
 
-    pub fn wait_for_key(&mut self, wanted: char, column: u16, row: u16) {
-        self.draw_box(column, row, 30, 2, false).unwrap();
+    fn key_box(&mut self, wanted: char, column: u16, row: u16) -> Result<(), Error> {
+        self.draw_box(column, row, 30, 2, false)?;
+        self.continue_box = Some(Rect::new(column, row, 30, 2));
         self.stdout
-            .queue(MoveTo(column + 2, row + 1))
-            .unwrap()
-            .queue(Print(format!("Premi `{}` per continuare...", wanted)))
-            .unwrap();
-        self.apply().unwrap();
+            .queue(MoveTo(column + 2, row + 1))?
+            .queue(Print(
+                self.theme.text(&(self.texts.press_to_continue)(wanted)),
+            ))?;
+        self.apply()
+    }
+
+    /// Waits for `wanted` or for a click on the box asking for it, giving
+    /// back `false` if the screen must be drawn again first.
+    fn wait_for_key(&mut self, wanted: char, players: usize) -> Result<bool, Error> {
         loop {
-            match read().unwrap() {
-                Event::Key(evt) => match evt.code {
-                    KeyCode::Char(character) => {
-                        if character == wanted {
-                            break;
-                        }
-                    }
-                    _ => {}
-                },
-                _ => {}
+            match self.next_input(players)? {
+                Input::Resized => return Ok(false),
+                Input::Key(KeyCode::Char(character)) if character == wanted => return Ok(true),
+                Input::Click(column, row) if self.clicked_continue(column, row) => return Ok(true),
+                Input::Key(_) | Input::Click(..) => {}
             }
         }
     }
 
-    pub fn draw_table(&mut self, game: &Game) {
-        self.clear().unwrap();
+    fn clicked_continue(&self, column: u16, row: u16) -> bool {
+        self.continue_box
+            .is_some_and(|area| area.contains(column, row))
+    }
 
-        self.table(&game.table, game.deck.len(), game.win_at)
-            .unwrap();
+    fn pick_card(&mut self, game: &Game, mut pointer: usize) -> Result<usize, Error> {
+        let player = game.current_player();
+        let ord = game.current_player_index;
+        loop {
+            if let Some(layout) = self.layout(game.players.len()) {
+                let (column, row) = layout.hand_pointer(ord, 0);
+                let (marker, blank) = if layout.compact {
+                    (self.theme.pointer().repeat(2), 15)
+                } else {
+                    (self.theme.pointer().repeat(5), 17)
+                };
+                let (pointer_column, _) = layout.hand_pointer(ord, pointer);
+                self.hint(&layout, &[self.theme.text(self.texts.help_hint)])?;
+                self.stdout
+                    .queue(MoveTo(column, row))?
+                    .queue(Print(" ".repeat(blank)))?
+                    .queue(MoveTo(pointer_column, row))?
+                    .queue(Print(marker))?
+                    .flush()?;
+            }
 
-        for (i, player) in game.players.iter().enumerate() {
-            self.player(player, i as u16, i == 0, i == game.current_player_index)
-                .unwrap();
+            match self.next_input(game.players.len())? {
+                Input::Resized => {
+                    let face_up = self.face_up.clone();
+                    self.draw_table(game, &face_up)?;
+                }
+                Input::Key(KeyCode::Left) => {
+                    if pointer > 0 {
+                        pointer -= 1;
+                    } else {
+                        pointer = player.hand.len() - 1;
+                    }
+                }
+                Input::Key(KeyCode::Right) => {
+                    if pointer < player.hand.len() - 1 {
+                        pointer += 1;
+                    } else {
+                        pointer = 0;
+                    }
+                }
+                Input::Key(KeyCode::Char('q')) | Input::Key(KeyCode::Char('c')) => self.quit(),
+                Input::Key(KeyCode::Char(' ')) | Input::Key(KeyCode::Enter) => {
+                    return Ok(pointer);
+                }
+                Input::Click(column, row) => {
+                    let clicked = self.layout(game.players.len()).and_then(|layout| {
+                        layout.hand_card_at(ord, player.hand.len(), column, row)
+                    });
+                    if let Some(clicked) = clicked {
+                        return Ok(clicked);
+                    }
+                }
+                Input::Key(_) => {}
+            }
         }
-
-        self.apply().unwrap();
     }
 
-    pub fn ask_for_card(&mut self, game: &Game) -> Result<String, Error> {
-        let player = game.current_player();
+    /// Highlights one set of `options` at a time on the table; `None` when
+    /// the player goes back to choose another card.
+    fn pick_capture(
+        &mut self,
+        game: &Game,
+        options: &[Vec<Card>],
+    ) -> Result<Option<Vec<Card>>, Error> {
         let mut pointer: usize = 0;
-        loop {
-            self.stdout
-                .queue(MoveTo(
-                    16,
-                    game.current_player_index as u16 * PLAYER_HEIGHT + 7,
-                ))?
-                .queue(Print("                 "))?
-                .queue(MoveTo(
-                    (16 + pointer * 6) as u16,
-                    game.current_player_index as u16 * PLAYER_HEIGHT + 7,
-                ))?
-                .queue(Print("▀▀▀▀▀"))?
-                .flush()?;
+        let choice = loop {
+            if let Some(layout) = self.layout(game.players.len()) {
+                self.hint(
+                    &layout,
+                    &[
+                        self.theme
+                            .text(&(self.texts.capture_of)(pointer + 1, options.len())),
+                        self.theme.text(self.texts.capture_keys),
+                    ],
+                )?;
+                self.highlight_table(&layout, &game.table, &options[pointer])?;
+                self.apply()?;
+            }
 
-            match read()? {
-                Event::Key(evt) => match evt.code {
-                    KeyCode::Left => {
-                        if pointer > 0 {
-                            pointer -= 1;
-                        } else {
-                            pointer = player.hand.len() - 1;
+            match self.next_input(game.players.len())? {
+                Input::Resized => {
+                    let face_up = self.face_up.clone();
+                    self.draw_table(game, &face_up)?;
+                }
+                Input::Key(KeyCode::Left) => {
+                    pointer = pointer.checked_sub(1).unwrap_or(options.len() - 1);
+                }
+                Input::Key(KeyCode::Right) => {
+                    pointer = (pointer + 1) % options.len();
+                }
+                Input::Key(KeyCode::Char('q')) | Input::Key(KeyCode::Char('c')) => self.quit(),
+                Input::Key(KeyCode::Char(' ')) | Input::Key(KeyCode::Enter) => {
+                    break Some(options[pointer].clone());
+                }
+                Input::Key(KeyCode::Esc) => break None,
+                Input::Click(column, row) => {
+                    let clicked = self
+                        .layout(game.players.len())
+                        .and_then(|layout| layout.table_card_at(game.table.len(), column, row))
+                        .map(|index| game.table[index]);
+                    // A card of the capture shown confirms it, any other
+                    // shows the next capture that takes it
+                    match clicked {
+                        Some(card) if options[pointer].contains(&card) => {
+                            break Some(options[pointer].clone());
                         }
-                    }
-                    KeyCode::Right => {
-                        if pointer < player.hand.len() - 1 {
-                            pointer += 1;
-                        } else {
-                            pointer = 0;
+                        Some(card) => {
+                            let next = (1..options.len())
+                                .map(|step| (pointer + step) % options.len())
+                                .find(|option| options[*option].contains(&card));
+                            pointer = next.unwrap_or(pointer);
                         }
+                        None => {}
                     }
-                    KeyCode::Char('q') | KeyCode::Char('c') => {
-                        self.reset(true);
-                        process::exit(0);
-                    }
-                    KeyCode::Char(' ') | KeyCode::Enter => {
-                        return Ok(player.hand[pointer].to_string());
-                    }
-                    _ => {}
-                },
-                _ => {}
+                }
+                Input::Key(_) => {}
+            }
+        };
+
+        if let Some(layout) = self.layout(game.players.len()) {
+            self.highlight_table(&layout, &game.table, &[])?;
+            self.hint(&layout, &[])?;
+            self.apply()?;
+        }
+        Ok(choice)
+    }
+
+    /// Writes `lines` where the keys are explained, clearing what was there.
+    fn hint(&mut self, layout: &Layout, lines: &[String]) -> Result<(), Error> {
+        let (column, row) = layout.hint;
+        for i in 0..2 {
+            let room = layout.room(column, row + i);
+            let line = lines.get(i as usize).map(String::as_str).unwrap_or("");
+            self.stdout
+                .queue(MoveTo(column, row + i))?
+                .queue(Print(format!("{:room$}", UI::fit(line, room))))?;
+        }
+        Ok(())
+    }
+
+    /// Marks the table cards in `selected`.
+    fn highlight_table(
+        &mut self,
+        layout: &Layout,
+        table: &[Card],
+        selected: &[Card],
+    ) -> Result<(), Error> {
+        for (i, card) in table.iter().enumerate() {
+            let (column, row) = layout.table_card(i);
+            if layout.compact {
+                self.card_label(card, column, row, true, selected.contains(card))?;
+            } else {
+                self.draw_box(column, row, 4, 3, selected.contains(card))?;
             }
         }
+        Ok(())
     }
 
     fn clear(&mut self) -> Result<(), Error> {
@@ -245,105 +607,187 @@ impl UI {
         height: u16,
         thick: bool,
     ) -> Result<(), Error> {
+        let borders = self.theme.borders(thick);
         self.stdout
             .queue(MoveTo(column, row))?
-            .queue(Print(if thick { "╔" } else { "┌" }))?
+            .queue(Print(borders.top_left))?
             .queue(MoveTo(column + width, row))?
-            .queue(Print(if thick { "╗" } else { "┐" }))?
+            .queue(Print(borders.top_right))?
             .queue(MoveTo(column, row + height))?
-            .queue(Print(if thick { "╚" } else { "└" }))?
+            .queue(Print(borders.bottom_left))?
             .queue(MoveTo(column + width, row + height))?
-            .queue(Print(if thick { "╝" } else { "┘" }))?;
+            .queue(Print(borders.bottom_right))?;
 
-        let horizontal = if thick { "═" } else { "─" };
         for i in 1..width {
             self.stdout
                 .queue(MoveTo(column + i, row))?
-                .queue(Print(horizontal))?
+                .queue(Print(borders.horizontal))?
                 .queue(MoveTo(column + i, row + height))?
-                .queue(Print(horizontal))?;
+                .queue(Print(borders.horizontal))?;
         }
 
-        let vertical = if thick { "║" } else { "│" };
         for i in 1..height {
             self.stdout
                 .queue(MoveTo(column, row + i))?
-                .queue(Print(vertical))?
+                .queue(Print(borders.vertical))?
                 .queue(MoveTo(column + width, row + i))?
-                .queue(Print(vertical))?;
+                .queue(Print(borders.vertical))?;
         }
 
         Ok(())
     }
 
-    fn table(&mut self, table: &Vec<Card>, deck: usize, win_at: u8) -> Result<(), Error> {
-        self.draw_box(40, 0, 30, 21, false)?;
+    fn table(
+        &mut self,
+        layout: &Layout,
+        table: &[Card],
+        deck: usize,
+        win_at: u8,
+    ) -> Result<(), Error> {
+        let area = layout.table;
+        self.draw_box(area.column, area.row, area.width, area.height, false)?;
+        let margin = if layout.compact { 2 } else { 6 };
         self.stdout
-            .queue(MoveTo(46, 1))?
-            .queue(Print(format!("Carte nel mazzo: {}", deck)))?
-            .queue(MoveTo(46, 20))?
-            .queue(Print(format!("Si vince a {} punti", win_at)))?;
-
-        table.iter().enumerate().for_each(|(i, card)| {
-            self.card(
-                card,
-                44 + (i % 4) as u16 * 6,
-                (5 + (i / 4) * 4) as u16,
-                true,
-            )
-            .unwrap();
-        });
+            .queue(MoveTo(area.column + margin, area.row + 1))?
+            .queue(Print(self.theme.text(&(self.texts.deck_left)(deck))))?
+            .queue(MoveTo(area.column + margin, area.row + area.height - 1))?
+            .queue(Print(self.theme.text(&(self.texts.win_at)(win_at))))?;
+
+        for (i, card) in table.iter().enumerate() {
+            let (column, row) = layout.table_card(i);
+            if layout.compact {
+                self.card_label(card, column, row, true, false)?;
+            } else {
+                self.card(card, column, row, true)?;
+            }
+        }
 
         Ok(())
     }
 
+    /// The latest entries of the game log, as many as fit in its panel.
+    fn log_panel(&mut self, layout: &Layout) -> Result<(), Error> {
+        let area = match layout.log {
+            Some(area) => area,
+            None => return Ok(()),
+        };
+        let width = (area.width - 3) as usize;
+        let mut lines: Vec<String> = Vec::new();
+        for entry in &self.log {
+            let chars: Vec<char> = entry.chars().collect();
+            for (i, chunk) in chars.chunks(width - 2).enumerate() {
+                let indent = if i == 0 { "" } else { "  " };
+                lines.push(format!("{}{}", indent, chunk.iter().collect::<String>()));
+            }
+        }
+        let shown = lines.len().saturating_sub(area.height as usize - 1);
+
+        self.draw_box(area.column, area.row, area.width, area.height, false)?;
+        self.stdout
+            .queue(MoveTo(area.column + 2, area.row))?
+            .queue(Print(self.theme.text(self.texts.log_title)))?;
+        for row in 1..area.height {
+            self.stdout
+                .queue(MoveTo(area.column + 1, area.row + row))?
+                .queue(Print(" ".repeat(width + 1)))?;
+        }
+        for (row, line) in lines[shown..].iter().enumerate() {
+            self.stdout
+                .queue(MoveTo(area.column + 2, area.row + row as u16 + 1))?
+                .queue(Print(line))?;
+        }
+        Ok(())
+    }
+
+    /// `text` split in lines of up to `width` characters, between words
+    /// when possible.
+    fn wrap(text: &str, width: usize) -> Vec<String> {
+        let mut lines: Vec<String> = Vec::new();
+        let mut line = String::new();
+        for word in text.split_whitespace() {
+            let length = line.chars().count();
+            if length > 0 && length + 1 + word.chars().count() > width {
+                lines.push(std::mem::take(&mut line));
+            }
+            if !line.is_empty() {
+                line.push(' ');
+            }
+            line.push_str(word);
+            while line.chars().count() > width {
+                let rest = line.chars().skip(width).collect();
+                lines.push(UI::fit(&line, width));
+                line = rest;
+            }
+        }
+        lines.push(line);
+        lines
+    }
+
+    /// `text` cut to `width` characters.
+    fn fit(text: &str, width: usize) -> String {
+        text.chars().take(width).collect()
+    }
+
     fn player(
         &mut self,
+        layout: &Layout,
         player: &Player,
-        ord: u16,
+        ord: usize,
         dealer: bool,
         active: bool,
+        face_up: bool,
     ) -> Result<(), Error> {
-        self.draw_box(0, ord * PLAYER_HEIGHT, 35, PLAYER_HEIGHT - 1, active)?;
+        let area = layout.player(ord);
+        self.draw_box(area.column, area.row, area.width, area.height, active)?;
 
         self.stdout
-            .queue(MoveTo(2, ord * PLAYER_HEIGHT + 1))?
-            .queue(Print(player.name.as_str().bold()))?;
+            .queue(MoveTo(2, area.row + 1))?
+            .queue(Print(UI::fit(&player.name, area.width as usize - 9).bold()))?;
 
         if dealer {
             self.stdout
-                .queue(MoveTo(29, ord * PLAYER_HEIGHT + 1))?
-                .queue(Print("MAZZO"))?;
+                .queue(MoveTo(area.width - 6, area.row + 1))?
+                .queue(Print(self.theme.text(self.texts.dealer)))?;
         }
 
-        player.hand.iter().enumerate().for_each(|(i, card)| {
-            self.card(
-                card,
-                16 + i as u16 * 6,
-                ord * PLAYER_HEIGHT + 3,
-                active || player.hand_visible,
-            )
-            .unwrap();
-        });
+        for (i, card) in player.hand.iter().enumerate() {
+            let (column, row) = layout.hand_card(ord, i);
+            let show = face_up || player.hand_visible;
+            if layout.compact {
+                self.card_label(card, column, row, show, false)?;
+            } else {
+                self.card(card, column, row, show)?;
+            }
+        }
 
-        player.effect.iter().enumerate().for_each(|(pos, effect)| {
+        let effects = player
+            .effect
+            .iter()
+            .map(|effect| self.theme.text(&(self.texts.effect)(effect)));
+        if layout.compact {
+            let effects: Vec<String> = effects.collect();
             self.stdout
-                .queue(MoveTo(2, ord * PLAYER_HEIGHT + 3 + pos as u16))
-                .unwrap()
-                .queue(Print(match &effect {
-                    Effect::DeckHandlerBroom(value) => format!("Banco a {}", value),
-                    Effect::Knocked(value) => format!("Bussa da {}", value),
-                }))
-                .unwrap();
-        });
+                .queue(MoveTo(17, area.row + 2))?
+                .queue(Print(UI::fit(&effects.join(", "), 12)))?;
+        } else {
+            for (pos, effect) in effects.enumerate() {
+                self.stdout
+                    .queue(MoveTo(2, area.row + 3 + pos as u16))?
+                    .queue(Print(effect))?;
+            }
+        }
 
+        let separator = if layout.compact { " " } else { "   " };
         self.stdout
-            .queue(MoveTo(2, ord * PLAYER_HEIGHT + 9))?
-            .queue(Print(format!(
-                "Carte: {}   Scope: {}   Punti: {}",
-                player.catched.len(),
-                player.brooms,
-                player.points
+            .queue(MoveTo(2, area.row + area.height - 1))?
+            .queue(Print(UI::fit(
+                &self.theme.text(&(self.texts.player_stats)(
+                    player.catched.len(),
+                    player.brooms,
+                    player.points,
+                    separator,
+                )),
+                area.width as usize - 3,
             )))?;
 
         Ok(())
@@ -353,27 +797,188 @@ impl UI {
         self.draw_box(column, row, 4, 3, false)?;
 
         if show {
-            let suit = match card {
-                Card::Heart(_) => " ♥ ",
-                Card::Diamond(_) => " ♦ ",
-                Card::Club(_) => " ♣ ",
-                Card::Spade(_) => " ♠ ",
-            };
-
+            let style = self.theme.style(card);
             self.stdout
-                .queue(SetForegroundColor(match card {
-                    Card::Heart(_) | Card::Diamond(_) => Color::Red,
-                    _ => Color::Blue,
-                }))?
                 .queue(MoveTo(column + 1, row + 1))?
-                .queue(Print(suit))?
+                .queue(Print(style.apply(format!(" {} ", self.theme.suit(card)))))?
                 .queue(MoveTo(column + 1, row + 2))?
                 .queue(Print("   "))?
                 .queue(MoveTo(column + 2, row + 2))?
-                .queue(Print(card.name()))?
-                .queue(SetForegroundColor(Color::Reset))?;
+                .queue(Print(style.apply(self.theme.rank(card))))?;
+        }
+
+        Ok(())
+    }
+
+    /// A card written on a single line, as the compact layout shows them.
+    fn card_label(
+        &mut self,
+        card: &Card,
+        column: u16,
+        row: u16,
+        show: bool,
+        selected: bool,
+    ) -> Result<(), Error> {
+        self.stdout.queue(MoveTo(column, row))?;
+        let label = self.theme.style(card).apply(self.theme.face(card));
+        if !show {
+            self.stdout.queue(Print(self.theme.back()))?;
+        } else if selected {
+            self.stdout.queue(Print(label.reverse()))?;
+        } else {
+            self.stdout.queue(Print(label))?;
+        }
+        Ok(())
+    }
+}
+
+impl Frontend for UI {
+    fn draw_table(&mut self, game: &Game, face_up: &[bool]) -> Result<(), Error> {
+        self.drawn_table = game.table.clone();
+        self.face_up = face_up.to_vec();
+        self.rules = self.texts.rules(game.win_at);
+        let layout = match self.layout(game.players.len()) {
+            Some(layout) => layout,
+            None => return self.too_small(game.players.len()),
+        };
+        self.clear()?;
+
+        self.table(&layout, &game.table, game.deck.len(), game.win_at)?;
+        self.log_panel(&layout)?;
+
+        for (i, player) in game.players.iter().enumerate() {
+            self.player(
+                &layout,
+                player,
+                i,
+                i == 0,
+                i == game.current_player_index,
+                face_up[i],
+            )?;
+        }
+
+        self.apply()
+    }
+
+    fn hand_over(&mut self, game: &Game, name: &str) -> Result<(), Error> {
+        loop {
+            self.draw_table(game, &vec![false; game.players.len()])?;
+            if let Some(layout) = self.layout(game.players.len()) {
+                let (column, row) = layout.message;
+                let message = UI::fit(
+                    &self.theme.text(&(self.texts.hand_over)(name)),
+                    layout.room(column, row + 1).saturating_sub(4),
+                );
+                let width = message.chars().count() as u16 + 3;
+                self.draw_box(column, row, width, 2, true)?;
+                self.continue_box = Some(Rect::new(column, row, width, 2));
+                self.stdout
+                    .queue(MoveTo(column + 2, row + 1))?
+                    .queue(Print(message.bold()))?;
+                self.apply()?;
+            }
+
+            loop {
+                match self.next_input(game.players.len())? {
+                    Input::Key(KeyCode::Char(' ')) => return Ok(()),
+                    Input::Click(column, row) if self.clicked_continue(column, row) => {
+                        return Ok(())
+                    }
+                    Input::Key(KeyCode::Char('q')) => self.quit(),
+                    Input::Key(_) | Input::Click(..) => {}
+                    Input::Resized => break,
+                }
+            }
         }
+    }
 
+    fn show_events(&mut self, game: &Game, events: &[GameEvent]) -> Result<(), Error> {
+        // Into the log, with the cards taken highlighted for a moment
+        let mut taken = Vec::new();
+        for event in events {
+            if let GameEvent::Played { taken: cards, .. } = event {
+                taken.extend_from_slice(cards);
+            }
+            self.log
+                .push(describe(self.texts, &self.theme, game, event));
+        }
+
+        // Otherwise the log is brought up to date by the next draw
+        let layout = self.layout(game.players.len());
+        if let (false, Some(layout)) = (taken.is_empty(), layout) {
+            let table = self.drawn_table.clone();
+            self.log_panel(&layout)?;
+            self.highlight_table(&layout, &table, &taken)?;
+            self.apply()?;
+            thread::sleep(CAPTURE_DELAY);
+        }
         Ok(())
     }
+
+    fn ask_for_card(&mut self, game: &Game) -> Result<(String, Vec<Card>), Error> {
+        let mut pointer: usize = 0;
+        loop {
+            pointer = self.pick_card(game, pointer)?;
+            let card = game.current_player().hand[pointer].to_string();
+            let mut options = game.capture_options(&card).unwrap_or_default();
+            if options.len() < 2 {
+                return Ok((card, options.pop().unwrap_or_default()));
+            }
+            if let Some(capture) = self.pick_capture(game, &options)? {
+                return Ok((card, capture));
+            }
+        }
+    }
+
+    fn show_hand_result(
+        &mut self,
+        result: &HandResult,
+        players_list: &[Player],
+    ) -> Result<(), Error> {
+        loop {
+            self.draw_hand_result(result, players_list)?;
+            if self.wait_for_key('c', players_list.len())? {
+                return Ok(());
+            }
+        }
+    }
+
+    fn draw_winner(&mut self, game: &Game) -> Result<(), Error> {
+        let mut points: Vec<(String, u8)> = game
+            .players
+            .iter()
+            .map(|p| (p.name.to_owned(), p.points))
+            .collect();
+        points.sort_unstable_by_key(|p| std::cmp::Reverse(p.1));
+
+        loop {
+            if self.layout(points.len()).is_none() {
+                self.too_small(points.len())?;
+            } else {
+                self.clear()?;
+                let last_line: u16 = game.players.len() as u16 + 7;
+                self.draw_box(0, 0, 28, last_line, true)?;
+                let title = self.texts.final_ranking.to_uppercase();
+                self.stdout
+                    .queue(MoveTo(5, 2))?
+                    .queue(Print(self.theme.text(&title).bold()))?;
+
+                for (i, (name, points)) in points.iter().enumerate() {
+                    self.stdout
+                        .queue(MoveTo(3, i as u16 + 4))?
+                        .queue(Print(UI::fit(
+                            &self
+                                .theme
+                                .text(&format!("{}° {} - {}", i + 1, name, points)),
+                            24,
+                        )))?;
+                }
+                self.key_box('q', 0, last_line + 1)?;
+            }
+
+            if self.wait_for_key('q', points.len())? {
+                self.quit();
+            }
+        }
+    }
 }
@@ -0,0 +1,256 @@
+/// A rectangle of the screen, in the same terms as the boxes drawn by the
+/// UI: the border stands on `column + width` and on `row + height` too.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Rect {
+    pub column: u16,
+    pub row: u16,
+    pub width: u16,
+    pub height: u16,
+}
+
+impl Rect {
+    pub fn new(column: u16, row: u16, width: u16, height: u16) -> Rect {
+        Rect {
+            column,
+            row,
+            width,
+            height,
+        }
+    }
+
+    /// Whether the cell at `column` and `row` is inside, border included.
+    pub fn contains(&self, column: u16, row: u16) -> bool {
+        (self.column..=self.column + self.width).contains(&column)
+            && (self.row..=self.row + self.height).contains(&row)
+    }
+}
+
+/// Where everything goes on a terminal of a given size. The full layout
+/// draws every card as a little box; the compact one, for smaller
+/// terminals, writes the cards as labels and packs the players tighter.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Layout {
+    pub compact: bool,
+    pub columns: u16,
+    pub rows: u16,
+    pub player_width: u16,
+    pub player_height: u16,
+    pub table: Rect,
+    /// The game log, when there is room for it
+    pub log: Option<Rect>,
+    /// Where to explain the keys to press
+    pub hint: (u16, u16),
+    /// Where messages that wait for a key are shown
+    pub message: (u16, u16),
+}
+
+const FULL_PLAYER_HEIGHT: u16 = 11;
+const COMPACT_PLAYER_HEIGHT: u16 = 6;
+const LOG_MAX_WIDTH: u16 = 46;
+const LOG_MIN_WIDTH: u16 = 30;
+const LOG_MIN_HEIGHT: u16 = 5;
+
+impl Layout {
+    /// The layout for a terminal of `columns` by `rows` with `players` at
+    /// the table; `None` when it does not fit at all.
+    pub fn new(columns: u16, rows: u16, players: usize) -> Option<Layout> {
+        let players = players as u16;
+        let layout = if columns >= 72 && rows >= (FULL_PLAYER_HEIGHT * players).max(27) {
+            Layout {
+                compact: false,
+                columns,
+                rows,
+                player_width: 35,
+                player_height: FULL_PLAYER_HEIGHT,
+                table: Rect::new(40, 0, 30, 21),
+                log: None,
+                hint: (41, 23),
+                message: (40, 24),
+            }
+        } else {
+            let (min_columns, min_rows) = Layout::minimum(players as usize);
+            if columns < min_columns || rows < min_rows {
+                return None;
+            }
+            Layout {
+                compact: true,
+                columns,
+                rows,
+                player_width: 30,
+                player_height: COMPACT_PLAYER_HEIGHT,
+                table: Rect::new(32, 0, 30, 7),
+                log: None,
+                hint: (33, 8),
+                message: (32, 9),
+            }
+        };
+        Some(layout.with_log())
+    }
+
+    /// The smallest terminal the game can be played on, as columns and rows.
+    pub fn minimum(players: usize) -> (u16, u16) {
+        let players = players as u16;
+        // The rows are needed by the players or by the hand results
+        (64, (COMPACT_PLAYER_HEIGHT * players).max(players + 14))
+    }
+
+    /// Puts the log beside the table, or else below the messages.
+    fn with_log(mut self) -> Layout {
+        let beside = self.table.column + self.table.width + 2;
+        let below = self.message.1 + 4;
+        if self.columns > beside + LOG_MIN_WIDTH {
+            let width = (self.columns - beside - 1).min(LOG_MAX_WIDTH);
+            let height = (self.rows - 1).min(21);
+            self.log = Some(Rect::new(beside, 0, width, height));
+        } else if self.rows > below + LOG_MIN_HEIGHT {
+            let height = self.rows - below - 1;
+            self.log = Some(Rect::new(
+                self.table.column,
+                below,
+                self.table.width,
+                height,
+            ));
+        }
+        self
+    }
+
+    /// The box of the player in the `ord` seat.
+    pub fn player(&self, ord: usize) -> Rect {
+        Rect::new(
+            0,
+            ord as u16 * self.player_height,
+            self.player_width,
+            self.player_height - 1,
+        )
+    }
+
+    /// Where the `index` card in the hand of the `ord` player is drawn.
+    pub fn hand_card(&self, ord: usize, index: usize) -> (u16, u16) {
+        let player = self.player(ord);
+        if self.compact {
+            (2 + index as u16 * 5, player.row + 2)
+        } else {
+            (16 + index as u16 * 6, player.row + 3)
+        }
+    }
+
+    /// Where the marker under the `index` card of the `ord` player goes.
+    pub fn hand_pointer(&self, ord: usize, index: usize) -> (u16, u16) {
+        let (column, row) = self.hand_card(ord, index);
+        if self.compact {
+            (column, row + 1)
+        } else {
+            (column, row + 4)
+        }
+    }
+
+    /// Where the `index` card on the table is drawn.
+    pub fn table_card(&self, index: usize) -> (u16, u16) {
+        let index = index as u16;
+        if self.compact {
+            (
+                self.table.column + 3 + (index % 5) * 5,
+                self.table.row + 2 + index / 5,
+            )
+        } else {
+            (
+                self.table.column + 4 + (index % 4) * 6,
+                self.table.row + 5 + (index / 4) * 4,
+            )
+        }
+    }
+
+    /// The cells taken by a card drawn at `position`.
+    fn card_area(&self, (column, row): (u16, u16)) -> Rect {
+        if self.compact {
+            Rect::new(column, row, 1, 0)
+        } else {
+            Rect::new(column, row, 4, 3)
+        }
+    }
+
+    /// Which of the `cards` in the hand of the `ord` player is at `column`
+    /// and `row`, if any.
+    pub fn hand_card_at(&self, ord: usize, cards: usize, column: u16, row: u16) -> Option<usize> {
+        (0..cards).find(|i| {
+            self.card_area(self.hand_card(ord, *i))
+                .contains(column, row)
+        })
+    }
+
+    /// Which of the `cards` on the table is at `column` and `row`, if any.
+    pub fn table_card_at(&self, cards: usize, column: u16, row: u16) -> Option<usize> {
+        (0..cards).find(|i| self.card_area(self.table_card(*i)).contains(column, row))
+    }
+
+    /// How many characters fit on `row` from `column`, without running into
+    /// the log or off the screen.
+    pub fn room(&self, column: u16, row: u16) -> usize {
+        let end = match self.log {
+            Some(log) if log.column > column && row >= log.row && row <= log.row + log.height => {
+                log.column - 1
+            }
+            _ => self.columns - 1,
+        };
+        end.saturating_sub(column) as usize
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fits(layout: &Layout, rect: Rect) -> bool {
+        rect.column + rect.width < layout.columns && rect.row + rect.height < layout.rows
+    }
+
+    #[test]
+    fn big_terminals_get_the_full_layout() {
+        let layout = Layout::new(120, 44, 4).unwrap();
+        assert!(!layout.compact);
+        assert_eq!(layout.table_card(5), (50, 9));
+        assert_eq!(layout.log, Some(Rect::new(72, 0, 46, 21)));
+        assert!((0..4).all(|ord| fits(&layout, layout.player(ord))));
+    }
+
+    #[test]
+    fn four_players_fit_a_classic_terminal() {
+        let layout = Layout::new(80, 24, 4).unwrap();
+        assert!(layout.compact);
+        assert!((0..4).all(|ord| fits(&layout, layout.player(ord))));
+        assert!(fits(&layout, layout.table));
+        assert!(layout.log.is_some_and(|log| fits(&layout, log)));
+    }
+
+    #[test]
+    fn the_log_goes_below_when_there_is_no_room_beside() {
+        let layout = Layout::new(64, 30, 2).unwrap();
+        let log = layout.log.unwrap();
+        assert_eq!(log.column, layout.table.column);
+        assert!(log.row > layout.message.1 + 2);
+        assert!(fits(&layout, log));
+        assert_eq!(Layout::new(64, 16, 2).unwrap().log, None);
+    }
+
+    #[test]
+    fn tiny_terminals_get_no_layout() {
+        assert_eq!(Layout::new(40, 24, 2), None);
+        assert_eq!(Layout::new(80, 20, 4), None);
+        let (columns, rows) = Layout::minimum(3);
+        assert!(Layout::new(columns, rows, 3).is_some());
+    }
+
+    #[test]
+    fn clicks_find_the_card_below() {
+        let full = Layout::new(120, 44, 2).unwrap();
+        assert_eq!(full.hand_card_at(1, 3, 24, 16), Some(1));
+        assert_eq!(full.hand_card_at(1, 3, 21, 16), None);
+        assert_eq!(full.hand_card_at(0, 3, 24, 16), None);
+        assert_eq!(full.table_card_at(6, 44, 12), Some(4));
+
+        let compact = Layout::new(80, 24, 4).unwrap();
+        assert_eq!(compact.hand_card_at(2, 3, 8, 14), Some(1));
+        assert_eq!(compact.hand_card_at(2, 3, 9, 14), None);
+        assert_eq!(compact.table_card_at(7, 36, 3), Some(5));
+    }
+}
@@ -0,0 +1,319 @@
+use super::{describe, Frontend, Theme};
+use cirulla_cli::i18n::{Lang, Texts};
+use cirulla_lib::{Card, Game, GameEvent, HandResult, Player};
+use std::{
+    io::{stdin, stdout, BufRead, Error, StdinLock, Stdout, Write},
+    process,
+};
+
+/// Tells the game a line at a time and reads the choices the same way, for
+/// screen readers, pipes and terminals that cannot be drawn on.
+pub struct PlainUI<R, W> {
+    input: R,
+    output: W,
+    texts: &'static Texts,
+    theme: Theme,
+}
+
+impl PlainUI<StdinLock<'static>, Stdout> {
+    pub fn new(lang: Lang, theme: Theme) -> Self {
+        PlainUI::with(stdin().lock(), stdout(), lang, theme)
+    }
+}
+
+impl<R: BufRead, W: Write> PlainUI<R, W> {
+    pub fn with(input: R, output: W, lang: Lang, theme: Theme) -> Self {
+        PlainUI {
+            input,
+            output,
+            texts: lang.texts(),
+            theme,
+        }
+    }
+
+    /// Reads a line, leaving the game on `q` or when the input is over.
+    fn read_line(&mut self) -> Result<String, Error> {
+        self.output.flush()?;
+        let mut line = String::new();
+        if self.input.read_line(&mut line)? == 0 || line.trim() == "q" {
+            process::exit(0);
+        }
+        Ok(line.trim().to_string())
+    }
+
+    /// Asks for a number from `min` to `max` until one is given.
+    fn choose(&mut self, prompt: &str, min: usize, max: usize) -> Result<usize, Error> {
+        loop {
+            let prompt = self.theme.text(&(self.texts.choose)(prompt, min, max));
+            write!(self.output, "{}", prompt)?;
+            match self.read_line()?.parse::<usize>() {
+                Ok(choice) if (min..=max).contains(&choice) => return Ok(choice),
+                _ => writeln!(
+                    self.output,
+                    "{}",
+                    self.theme.text(&(self.texts.write_a_number)(min, max))
+                )?,
+            }
+        }
+    }
+
+    /// The cards on the table, or that it is empty.
+    fn table(&self, game: &Game) -> String {
+        if game.table.is_empty() {
+            self.texts.empty.to_string()
+        } else {
+            game.table
+                .iter()
+                .map(|card| self.theme.label(card))
+                .collect::<Vec<_>>()
+                .join(" ")
+        }
+    }
+
+    fn numbered<T>(items: &[T], text: impl Fn(&T) -> String) -> String {
+        items
+            .iter()
+            .enumerate()
+            .map(|(i, item)| format!("{}) {}", i + 1, text(item)))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+impl<R: BufRead, W: Write> Frontend for PlainUI<R, W> {
+    fn draw_table(&mut self, game: &Game, face_up: &[bool]) -> Result<(), Error> {
+        let current = game.current_player_index;
+        writeln!(self.output)?;
+        writeln!(
+            self.output,
+            "{}",
+            self.theme
+                .text(&(self.texts.turn)(&game.players[current].name))
+        )?;
+        let scores: Vec<String> = game
+            .players
+            .iter()
+            .map(|p| (self.texts.score)(&p.name, p.points, p.brooms, p.catched.len()))
+            .collect();
+        writeln!(
+            self.output,
+            "{}",
+            self.theme.text(&(self.texts.table_status)(
+                &scores.join("; "),
+                game.deck.len(),
+                game.win_at
+            ))
+        )?;
+        // A hand face up is asked for together with the table
+        if !face_up[current] {
+            let table = self.table(game);
+            writeln!(
+                self.output,
+                "{}",
+                self.theme.text(&(self.texts.table_cards)(&table))
+            )?;
+        }
+        Ok(())
+    }
+
+    fn hand_over(&mut self, _game: &Game, name: &str) -> Result<(), Error> {
+        writeln!(self.output)?;
+        write!(
+            self.output,
+            "{}",
+            self.theme.text(&(self.texts.hand_over_plain)(name))
+        )?;
+        self.read_line()?;
+        Ok(())
+    }
+
+    fn show_events(&mut self, game: &Game, events: &[GameEvent]) -> Result<(), Error> {
+        for event in events {
+            writeln!(
+                self.output,
+                "{}.",
+                describe(self.texts, &self.theme, game, event)
+            )?;
+        }
+        Ok(())
+    }
+
+    fn ask_for_card(&mut self, game: &Game) -> Result<(String, Vec<Card>), Error> {
+        let player = game.current_player();
+        let table = self.table(game);
+        writeln!(
+            self.output,
+            "{}",
+            self.theme.text(&(self.texts.table_and_hand)(
+                &table,
+                &PlainUI::<R, W>::numbered(&player.hand, |card| self.theme.label(card))
+            ))
+        )?;
+        for other in game
+            .players
+            .iter()
+            .filter(|p| p.hand_visible && p.id != player.id)
+        {
+            let hand: Vec<String> = other
+                .hand
+                .iter()
+                .map(|card| self.theme.label(card))
+                .collect();
+            writeln!(
+                self.output,
+                "{}",
+                self.theme
+                    .text(&(self.texts.shows)(&other.name, &hand.join(" ")))
+            )?;
+        }
+
+        loop {
+            let choice = self.choose(self.texts.card_to_play, 1, player.hand.len())?;
+            let card = player.hand[choice - 1].to_string();
+            let mut options = game.capture_options(&card).unwrap_or_default();
+            if options.len() < 2 {
+                return Ok((card, options.pop().unwrap_or_default()));
+            }
+
+            writeln!(
+                self.output,
+                "{}: {}",
+                self.theme.text(self.texts.possible_captures),
+                PlainUI::<R, W>::numbered(&options, |option| self.theme.labels(option))
+            )?;
+            let choice = self.choose(self.texts.capture_to_make, 0, options.len())?;
+            if choice > 0 {
+                return Ok((card, options.swap_remove(choice - 1)));
+            }
+        }
+    }
+
+    fn show_hand_result(&mut self, result: &HandResult, players: &[Player]) -> Result<(), Error> {
+        let name = |id: &Option<String>| {
+            id.as_ref()
+                .and_then(|id| players.iter().find(|p| p.id == *id))
+                .map(|p| p.name.clone())
+                .unwrap_or_else(|| self.texts.nobody.to_string())
+        };
+        let low_ladder = match result.low_ladder_value {
+            0 => name(&result.low_ladder),
+            value => format!("{} ({})", name(&result.low_ladder), value),
+        };
+
+        let points: Vec<String> = players
+            .iter()
+            .map(|p| format!("{} {}", p.name, p.points))
+            .collect();
+        let lines = [
+            (self.texts.cards, name(&result.cards)),
+            (self.texts.diamonds, name(&result.diamonds)),
+            (self.texts.primiera, name(&result.primiera)),
+            (
+                self.texts.pretty_seven,
+                name(&Some(result.pretty_seven.clone())),
+            ),
+            (self.texts.high_ladder, name(&result.high_ladder)),
+            (self.texts.low_ladder, low_ladder),
+            (self.texts.points, points.join(", ")),
+        ];
+
+        writeln!(self.output)?;
+        writeln!(self.output, "{}", self.theme.text(self.texts.hand_end))?;
+        for (title, who) in lines {
+            let line = format!("{} {}", title, who);
+            writeln!(self.output, "{}", self.theme.text(&line))?;
+        }
+        write!(self.output, "{}", self.theme.text(self.texts.press_enter))?;
+        self.read_line()?;
+        Ok(())
+    }
+
+    fn draw_winner(&mut self, game: &Game) -> Result<(), Error> {
+        let mut points: Vec<(&str, u8)> = game
+            .players
+            .iter()
+            .map(|p| (p.name.as_str(), p.points))
+            .collect();
+        points.sort_unstable_by_key(|p| std::cmp::Reverse(p.1));
+
+        writeln!(self.output)?;
+        writeln!(
+            self.output,
+            "{}:",
+            self.theme.text(self.texts.final_ranking)
+        )?;
+        for (i, (name, points)) in points.iter().enumerate() {
+            let line = format!("{}° {} - {}", i + 1, name, points);
+            writeln!(self.output, "{}", self.theme.text(&line))?;
+        }
+        self.output.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn game() -> Game {
+        let mut game = Game::new(51);
+        game.add_player("Anna", None).unwrap();
+        game.add_player("Bepi", None).unwrap();
+        game.start_game().unwrap();
+        game.start_hand().unwrap();
+        game.take_events();
+        game.players[0].hand = vec![Card::Heart(1), Card::Spade(5), Card::Diamond(7)];
+        game.table = vec![Card::Club(3), Card::Heart(4), Card::Spade(2)];
+        game
+    }
+
+    fn ask(game: &Game, input: &str) -> ((String, Vec<Card>), String) {
+        let mut output = Vec::new();
+        let choice = PlainUI::with(Cursor::new(input), &mut output, Lang::It, Theme::default())
+            .ask_for_card(game)
+            .unwrap();
+        (choice, String::from_utf8(output).unwrap())
+    }
+
+    #[test]
+    fn the_table_and_the_hand_are_written_out() {
+        let (choice, output) = ask(&game(), "4\nfive\n2\n");
+        assert!(output.starts_with("Tavolo: 3♣ 4♥ 2♠; la tua mano: 1) A♥ 2) 5♠ 3) 7♦\n"));
+        assert_eq!(output.matches("Scrivi un numero da 1 a 3.").count(), 2);
+        assert_eq!(
+            choice,
+            ("5s".to_string(), vec![Card::Club(3), Card::Spade(2)])
+        );
+    }
+
+    #[test]
+    fn the_table_is_public_but_a_face_down_hand_is_not() {
+        let mut output = Vec::new();
+        PlainUI::with(Cursor::new(""), &mut output, Lang::It, Theme::default())
+            .draw_table(&game(), &[false, false])
+            .unwrap();
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.contains("Anna 0 punti, 0 scope, 0 carte; Bepi 0 punti"));
+        assert!(output.contains("Tavolo: 3♣ 4♥ 2♠.\n"));
+        assert!(!output.contains("A♥"));
+    }
+
+    #[test]
+    fn the_capture_is_asked_when_there_is_a_choice() {
+        let mut game = game();
+        game.table = vec![
+            Card::Club(3),
+            Card::Heart(4),
+            Card::Spade(2),
+            Card::Diamond(5),
+        ];
+
+        let (choice, output) = ask(&game, "3\n0\n3\n3\n");
+        assert!(output.contains("Prese possibili: 1) 3♣ + 4♥ 2) 3♣ + 5♦ 3) 2♠ + 5♦\n"));
+        assert_eq!(output.matches("Prese possibili").count(), 2);
+        assert_eq!(
+            choice,
+            ("7d".to_string(), vec![Card::Spade(2), Card::Diamond(5)])
+        );
+    }
+}
@@ -0,0 +1,338 @@
+use cirulla_lib::Card;
+use clap::ValueEnum;
+use crossterm::style::{Attribute, Color, ContentStyle, Stylize};
+use serde::Deserialize;
+
+/// The colours the cards are drawn with.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum Palette {
+    /// Red hearts and diamonds, blue clubs and spades
+    #[default]
+    Classic,
+    /// A colour for every suit, as in the four-colour decks
+    FourColor,
+    /// Bold cards in the terminal's own colour, told apart by their suit
+    HighContrast,
+}
+
+/// How the suits are named.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum Suits {
+    /// Hearts, diamonds, clubs and spades, with J, Q and K
+    #[default]
+    French,
+    /// Coppe, denari, bastoni and spade, with fante, cavallo and re
+    Regional,
+}
+
+/// How the cards and the table look.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Theme {
+    /// Only ASCII characters, for terminals without Unicode
+    pub ascii: bool,
+    pub palette: Palette,
+    pub suits: Suits,
+}
+
+/// The corners, the horizontal and the vertical side of a box.
+pub struct Borders {
+    pub top_left: &'static str,
+    pub top_right: &'static str,
+    pub bottom_left: &'static str,
+    pub bottom_right: &'static str,
+    pub horizontal: &'static str,
+    pub vertical: &'static str,
+}
+
+impl Theme {
+    /// The suit as drawn on the card faces, one character wide.
+    pub fn suit(&self, card: &Card) -> &'static str {
+        match (self.suits, self.ascii, card) {
+            // The initials of coppe, denari, bastoni and spade
+            (Suits::Regional, _, Card::Heart(_)) => "c",
+            (Suits::Regional, _, Card::Diamond(_)) => "d",
+            (Suits::Regional, _, Card::Club(_)) => "b",
+            (Suits::Regional, _, Card::Spade(_)) => "s",
+            (Suits::French, true, Card::Heart(_)) => "h",
+            (Suits::French, true, Card::Diamond(_)) => "d",
+            (Suits::French, true, Card::Club(_)) => "c",
+            (Suits::French, true, Card::Spade(_)) => "s",
+            (Suits::French, false, Card::Heart(_)) => "♥",
+            (Suits::French, false, Card::Diamond(_)) => "♦",
+            (Suits::French, false, Card::Club(_)) => "♣",
+            (Suits::French, false, Card::Spade(_)) => "♠",
+        }
+    }
+
+    pub fn rank(&self, card: &Card) -> String {
+        match (self.suits, card.value()) {
+            (Suits::Regional, 8) => "F".to_string(),
+            (Suits::Regional, 9) => "C".to_string(),
+            (Suits::Regional, 10) => "R".to_string(),
+            _ => card.name(),
+        }
+    }
+
+    /// The card named with its suit in full where there is room: in the
+    /// log, in the plain front end and in the list of the captures.
+    pub fn label(&self, card: &Card) -> String {
+        match (self.suits, card) {
+            (Suits::Regional, Card::Heart(_)) => format!("{} coppe", self.rank(card)),
+            (Suits::Regional, Card::Diamond(_)) => format!("{} denari", self.rank(card)),
+            (Suits::Regional, Card::Club(_)) => format!("{} bastoni", self.rank(card)),
+            (Suits::Regional, Card::Spade(_)) => format!("{} spade", self.rank(card)),
+            (Suits::French, _) => self.face(card),
+        }
+    }
+
+    /// The card in two or three characters, for the compact layout.
+    pub fn face(&self, card: &Card) -> String {
+        format!("{}{}", self.rank(card), self.suit(card))
+    }
+
+    pub fn labels(&self, cards: &[Card]) -> String {
+        cards
+            .iter()
+            .map(|card| self.label(card))
+            .collect::<Vec<_>>()
+            .join(" + ")
+    }
+
+    /// How `card` is written: its colour, and bold in high contrast.
+    pub fn style(&self, card: &Card) -> ContentStyle {
+        let color = match (self.palette, card) {
+            (Palette::Classic, Card::Heart(_) | Card::Diamond(_)) => Color::Red,
+            (Palette::Classic, _) => Color::Blue,
+            (Palette::FourColor, Card::Heart(_)) => Color::Red,
+            (Palette::FourColor, Card::Diamond(_)) => Color::Blue,
+            (Palette::FourColor, Card::Club(_)) => Color::Green,
+            (Palette::FourColor, Card::Spade(_)) => Color::Reset,
+            (Palette::HighContrast, _) => Color::Reset,
+        };
+        let style = ContentStyle::new().with(color);
+        if self.palette == Palette::HighContrast {
+            style.attribute(Attribute::Bold)
+        } else {
+            style
+        }
+    }
+
+    pub fn borders(&self, thick: bool) -> Borders {
+        match (self.ascii, thick) {
+            (false, false) => Borders {
+                top_left: "┌",
+                top_right: "┐",
+                bottom_left: "└",
+                bottom_right: "┘",
+                horizontal: "─",
+                vertical: "│",
+            },
+            (false, true) => Borders {
+                top_left: "╔",
+                top_right: "╗",
+                bottom_left: "╚",
+                bottom_right: "╝",
+                horizontal: "═",
+                vertical: "║",
+            },
+            (true, false) => Borders {
+                top_left: "+",
+                top_right: "+",
+                bottom_left: "+",
+                bottom_right: "+",
+                horizontal: "-",
+                vertical: "|",
+            },
+            (true, true) => Borders {
+                top_left: "#",
+                top_right: "#",
+                bottom_left: "#",
+                bottom_right: "#",
+                horizontal: "=",
+                vertical: "#",
+            },
+        }
+    }
+
+    /// What a card lying face down looks like, in the compact layout.
+    pub fn back(&self) -> &'static str {
+        if self.ascii {
+            "##"
+        } else {
+            "▒▒"
+        }
+    }
+
+    /// The mark under the card about to be played.
+    pub fn pointer(&self) -> &'static str {
+        if self.ascii {
+            "^"
+        } else {
+            "▀"
+        }
+    }
+
+    /// `text` written with ASCII characters only, when asked to: accented
+    /// letters lose their accent and every other symbol becomes one that
+    /// takes the same room, so that the layout does not change.
+    pub fn text(&self, text: &str) -> String {
+        if !self.ascii {
+            return text.to_string();
+        }
+        text.chars()
+            .map(|c| match c {
+                c if c.is_ascii() => c,
+                'à' | 'á' | 'â' | 'ä' => 'a',
+                'è' | 'é' | 'ê' | 'ë' => 'e',
+                'ì' | 'í' | 'î' | 'ï' => 'i',
+                'ò' | 'ó' | 'ô' | 'ö' => 'o',
+                'ù' | 'ú' | 'û' | 'ü' => 'u',
+                'À' | 'Á' | 'Â' | 'Ä' => 'A',
+                'È' | 'É' | 'Ê' | 'Ë' => 'E',
+                'Ì' | 'Í' | 'Î' | 'Ï' => 'I',
+                'Ò' | 'Ó' | 'Ô' | 'Ö' => 'O',
+                'Ù' | 'Ú' | 'Û' | 'Ü' => 'U',
+                '←' => '<',
+                '→' => '>',
+                '—' | '–' => '-',
+                '°' => '.',
+                '’' | '‘' => '\'',
+                '“' | '”' | '«' | '»' => '"',
+                _ => '?',
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cirulla_cli::i18n::{Lang, Texts};
+    use cirulla_lib::{Effect, GameError};
+
+    /// Every text of the catalog, with the values filled in.
+    fn every_text(texts: &Texts) -> Vec<String> {
+        let mut all: Vec<String> = [
+            texts.broom,
+            texts.too_small,
+            texts.widen_or_quit,
+            texts.cards,
+            texts.diamonds,
+            texts.primiera,
+            texts.pretty_seven,
+            texts.high_ladder,
+            texts.low_ladder,
+            texts.points,
+            texts.capture_keys,
+            texts.log_title,
+            texts.dealer,
+            texts.final_ranking,
+            texts.empty,
+            texts.card_to_play,
+            texts.possible_captures,
+            texts.capture_to_make,
+            texts.nobody,
+            texts.hand_end,
+            texts.press_enter,
+            texts.help_hint,
+            texts.help_close,
+            texts.controls_title,
+            texts.rules_title,
+        ]
+        .iter()
+        .chain(texts.controls)
+        .map(|text| text.to_string())
+        .collect();
+        all.extend(texts.rules(51));
+        all.extend([
+            (texts.new_hand)("Anna"),
+            (texts.effect)(&Effect::Knocked(9)),
+            (texts.effect)(&Effect::DeckHandlerBroom(10)),
+            (texts.plays)("Anna", "7d"),
+            (texts.takes)("7d"),
+            (texts.last_take)("Anna", "7d"),
+            (texts.needed_size)(80, 24, 40, 12),
+            (texts.press_to_continue)('c'),
+            (texts.capture_of)(1, 2),
+            (texts.deck_left)(30),
+            (texts.win_at)(51),
+            (texts.player_stats)(10, 1, 5, " "),
+            (texts.hand_over)("Anna"),
+            (texts.turn)("Anna"),
+            (texts.score)("Anna", 5, 1, 10),
+            (texts.table_status)("Anna 5", 30, 51),
+            (texts.hand_over_plain)("Anna"),
+            (texts.table_and_hand)("7d", "1) 7d"),
+            (texts.table_cards)("7d"),
+            (texts.shows)("Anna", "7d"),
+            (texts.choose)("Carta", 1, 3),
+            (texts.write_a_number)(1, 3),
+        ]);
+        all.extend(
+            [
+                GameError::GameAlreadyStarted,
+                GameError::TooManyPlayers,
+                GameError::NameTooShort,
+                GameError::NameAlreadyTaken,
+                GameError::NotEnoughPlayers,
+                GameError::GameNotStarted,
+                GameError::DeckNotReady,
+                GameError::HandNotStarted,
+                GameError::CardNotFound,
+                GameError::InvalidSeed,
+                GameError::InvalidCapture,
+            ]
+            .iter()
+            .map(texts.game_error),
+        );
+        all
+    }
+
+    #[test]
+    fn cards_are_named_by_the_theme() {
+        let card = Card::Club(9);
+        assert_eq!(Theme::default().label(&card), "Q♣");
+        let ascii = Theme {
+            ascii: true,
+            ..Theme::default()
+        };
+        assert_eq!(ascii.label(&card), "Qc");
+        let regional = Theme {
+            suits: Suits::Regional,
+            ..Theme::default()
+        };
+        assert_eq!(
+            regional.labels(&[card, Card::Heart(7)]),
+            "C bastoni + 7 coppe"
+        );
+        assert_eq!(regional.face(&card), "Cb");
+    }
+
+    #[test]
+    fn every_text_can_be_written_in_ascii() {
+        let ascii = Theme {
+            ascii: true,
+            ..Theme::default()
+        };
+        for lang in [Lang::It, Lang::En] {
+            for text in every_text(lang.texts()) {
+                let written = ascii.text(&text);
+                assert!(written.is_ascii(), "{}", written);
+                assert_eq!(written.chars().count(), text.chars().count());
+            }
+        }
+        assert_eq!(ascii.text("è già così"), "e gia cosi");
+    }
+
+    #[test]
+    fn themes_are_read_from_toml() {
+        let theme: Theme = toml::from_str("ascii = true\npalette = \"four-color\"").unwrap();
+        assert!(theme.ascii);
+        assert_eq!(theme.palette, Palette::FourColor);
+        assert_eq!(theme.suits, Suits::French);
+        assert!(toml::from_str::<Theme>("colours = \"pink\"").is_err());
+    }
+}
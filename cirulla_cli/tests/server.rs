@@ -0,0 +1,585 @@
+use cirulla_cli::server::{HeartbeatConfig, Limits, Server, ServerConfig, ServerHandle};
+use serde_json::Value;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader, Lines};
+use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
+use tokio::net::TcpStream;
+use tokio::time::timeout;
+
+/// A player talking to the server the way a terminal client would.
+struct Client {
+    lines: Lines<BufReader<OwnedReadHalf>>,
+    writer: OwnedWriteHalf,
+}
+
+impl Client {
+    async fn connect(server: &ServerHandle) -> Client {
+        let stream = TcpStream::connect(server.local_addrs()[0]).await.unwrap();
+        let (reader, writer) = stream.into_split();
+        Client {
+            lines: BufReader::new(reader).lines(),
+            writer,
+        }
+    }
+
+    async fn send(&mut self, command: &str) {
+        self.writer
+            .write_all(format!("{}\n", command).as_bytes())
+            .await
+            .unwrap();
+    }
+
+    /// Next line from the server, or None once it closed the connection.
+    async fn read(&mut self) -> Option<String> {
+        timeout(Duration::from_secs(5), self.lines.next_line())
+            .await
+            .expect("The server did not answer in time")
+            .unwrap()
+    }
+
+    async fn expect(&mut self, expected: &str) {
+        assert_eq!(self.read().await.as_deref(), Some(expected));
+    }
+
+    async fn read_until(&mut self, prefix: &str) -> String {
+        loop {
+            let line = self.read().await.expect("Disconnected");
+            if line.starts_with(prefix) {
+                return line;
+            }
+        }
+    }
+
+    /// Every line up to and including the first one starting with `prefix`.
+    async fn lines_until(&mut self, prefix: &str) -> Vec<String> {
+        let mut lines = Vec::new();
+        loop {
+            let line = self.read().await.expect("Disconnected");
+            let last = line.starts_with(prefix);
+            lines.push(line);
+            if last {
+                return lines;
+            }
+        }
+    }
+
+    /// Reads up to the next PLAY, WAIT or GAME END, giving it back along
+    /// with the last game status received.
+    async fn read_turn(&mut self, status: &mut Option<Value>) -> String {
+        loop {
+            let line = self.read().await.expect("Disconnected");
+            match line.as_str() {
+                "PLAY" | "WAIT" | "GAME END" => return line,
+                "GAME STATUS START" => {
+                    let mut json = String::new();
+                    loop {
+                        let line = self.read().await.expect("Disconnected");
+                        if line == "GAME STATUS END" {
+                            break;
+                        }
+                        json.push_str(&line);
+                    }
+                    *status = Some(serde_json::from_str(&json).unwrap());
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+async fn start_server() -> ServerHandle {
+    start_server_with(ServerConfig::default()).await
+}
+
+/// Starts `config` on a free local port.
+async fn start_server_with(config: ServerConfig) -> ServerHandle {
+    let config = ServerConfig {
+        listen: vec!["127.0.0.1:0".to_string()],
+        ..config
+    };
+    Server::bind(config, None).await.unwrap().spawn()
+}
+
+#[tokio::test]
+async fn two_players_play_until_the_game_ends() {
+    let server = start_server().await;
+    let mut anna = Client::connect(&server).await;
+    let mut bepi = Client::connect(&server).await;
+
+    anna.send("HELLO Anna").await;
+    anna.expect("HI Anna").await;
+    bepi.send("HELLO Anna").await;
+    bepi.expect("ERROR: name already in use").await;
+    bepi.send("HELLO Bepi").await;
+    bepi.expect("HI Bepi").await;
+
+    anna.send("TABLE NEW \"End to end\" 2 11").await;
+    let joined = anna.read_until("TABLE JOINED").await;
+    let table_id = joined.trim_start_matches("TABLE JOINED ").to_string();
+    bepi.send(&format!("TABLE JOIN {}", table_id)).await;
+    bepi.read_until("TABLE JOINED").await;
+
+    anna.read_until("GAME START").await;
+    bepi.read_until("GAME START").await;
+
+    let mut players = [anna, bepi];
+    let mut statuses = [None, None];
+    let mut plays = 0;
+    loop {
+        let mut turns = Vec::new();
+        for (player, status) in players.iter_mut().zip(statuses.iter_mut()) {
+            turns.push(player.read_turn(status).await);
+        }
+        if turns.iter().any(|turn| turn == "GAME END") {
+            assert!(turns.iter().all(|turn| turn == "GAME END"));
+            break;
+        }
+
+        let playing = turns.iter().position(|turn| turn == "PLAY").unwrap();
+        let card = statuses[playing].as_ref().unwrap()["hand"][0]
+            .as_str()
+            .unwrap()
+            .to_string();
+        if plays == 0 {
+            let waiting = &mut players[1 - playing];
+            waiting.send(&format!("PLAY {}", card)).await;
+            assert_eq!(waiting.read_until("ERROR").await, "ERROR: not your turn");
+        }
+        players[playing].send(&format!("PLAY {}", card)).await;
+        plays += 1;
+    }
+    assert!(plays >= 18, "a game lasts at least one full hand");
+
+    server.stop().await;
+}
+
+#[tokio::test]
+async fn stopping_the_server_notifies_the_clients() {
+    let server = start_server().await;
+    let mut anna = Client::connect(&server).await;
+    anna.send("HELLO Anna").await;
+    anna.expect("HI Anna").await;
+
+    server.stop().await;
+
+    anna.expect("SHUTDOWN").await;
+    assert_eq!(anna.read().await, None);
+}
+
+#[tokio::test]
+async fn errors_are_told_in_the_language_of_the_session() {
+    let server = start_server().await;
+    let mut anna = Client::connect(&server).await;
+    anna.send("PLAY 7d").await;
+    anna.expect("ERROR: table not found").await;
+
+    anna.send("LANG it").await;
+    anna.expect("LANG it").await;
+    anna.send("PLAY 7d").await;
+    anna.expect("ERROR: tavolo non trovato").await;
+    anna.send("LANG klingon").await;
+    anna.expect("ERROR: la lingua deve essere una tra: it, en")
+        .await;
+
+    anna.send("LANG en").await;
+    anna.expect("LANG en").await;
+    anna.send("DANCE").await;
+    anna.expect("ERROR: invalid command").await;
+
+    server.stop().await;
+}
+
+#[tokio::test]
+async fn sessions_that_do_not_answer_ping_are_dropped() {
+    let server = start_server_with(ServerConfig {
+        heartbeat: HeartbeatConfig {
+            ping_interval: Duration::from_millis(100),
+            idle_timeout: Duration::from_millis(500),
+            write_timeout: Duration::from_millis(500),
+        },
+        ..ServerConfig::default()
+    })
+    .await;
+    let mut anna = Client::connect(&server).await;
+    let mut bepi = Client::connect(&server).await;
+    anna.send("HELLO Anna").await;
+    anna.expect("HI Anna").await;
+    bepi.send("HELLO Bepi").await;
+    bepi.expect("HI Bepi").await;
+
+    // Anna answers every PING, Bepi lets them pile up until dropped
+    let started = std::time::Instant::now();
+    while started.elapsed() < Duration::from_secs(1) {
+        anna.read_until("PING").await;
+        anna.send("PONG").await;
+    }
+    loop {
+        match bepi.read().await.as_deref() {
+            Some("PING") => {}
+            Some(line) => panic!("Unexpected line {}", line),
+            None => break,
+        }
+    }
+
+    // Still there, and alone
+    anna.send("WHO").await;
+    anna.read_until("WHO START").await;
+    assert!(anna.read().await.unwrap().ends_with(" Anna"));
+    anna.expect("WHO END").await;
+
+    server.stop().await;
+}
+
+/// Anna and Bepi, both said HELLO to `server`.
+async fn anna_and_bepi(server: &ServerHandle) -> (Client, Client) {
+    let mut anna = Client::connect(server).await;
+    anna.send("HELLO Anna").await;
+    anna.expect("HI Anna").await;
+    let mut bepi = Client::connect(server).await;
+    bepi.send("HELLO Bepi").await;
+    bepi.expect("HI Bepi").await;
+    (anna, bepi)
+}
+
+#[tokio::test]
+async fn chat_can_be_muted_and_is_limited() {
+    let server = start_server_with(ServerConfig {
+        limits: Limits {
+            chat_messages: 3,
+            chat_max_length: 10,
+            ..Limits::default()
+        },
+        ..ServerConfig::default()
+    })
+    .await;
+    let (mut anna, mut bepi) = anna_and_bepi(&server).await;
+
+    anna.send("SCREAM one").await;
+    anna.expect("SCREAM FROM Anna: one").await;
+    bepi.expect("SCREAM FROM Anna: one").await;
+
+    bepi.send("MUTE Anna").await;
+    bepi.expect("MUTED Anna").await;
+    bepi.send("MUTE Carlo").await;
+    bepi.expect("ERROR: user not found").await;
+    anna.send("SCREAM two").await;
+    anna.expect("SCREAM FROM Anna: two").await;
+    // Had Bepi received the second message it would come before this
+    bepi.send("UNMUTE Anna").await;
+    bepi.expect("UNMUTED Anna").await;
+
+    bepi.send("SCREAM far too long to be said").await;
+    bepi.expect("ERROR: message longer than 10 characters")
+        .await;
+
+    anna.send("SCREAM three").await;
+    anna.expect("SCREAM FROM Anna: three").await;
+    bepi.expect("SCREAM FROM Anna: three").await;
+    anna.send("SCREAM four").await;
+    anna.expect("ERROR: too many messages, slow down").await;
+
+    server.stop().await;
+}
+
+#[tokio::test]
+async fn table_say_and_whisper_reach_only_their_recipients() {
+    let server = start_server().await;
+    let (mut anna, mut bepi) = anna_and_bepi(&server).await;
+    let mut carlo = Client::connect(&server).await;
+    carlo.send("HELLO Carlo").await;
+    carlo.expect("HI Carlo").await;
+
+    anna.send("TABLE NEW \"Chat\" 3 11").await;
+    let joined = anna.read_until("TABLE JOINED").await;
+    let table_id = joined.trim_start_matches("TABLE JOINED ").to_string();
+    bepi.send(&format!("TABLE JOIN {}", table_id)).await;
+    bepi.read_until("TABLE JOINED").await;
+
+    anna.send("TABLE SAY who deals?").await;
+    anna.read_until("TABLE SAY FROM Anna: who deals?").await;
+    bepi.read_until("TABLE SAY FROM Anna: who deals?").await;
+    carlo.send("TABLE SAY me!").await;
+    let lines = carlo.lines_until("ERROR").await;
+    assert_eq!(lines.last().unwrap(), "ERROR: table not found");
+    assert!(lines.iter().all(|line| !line.starts_with("TABLE SAY")));
+
+    carlo.send("WHISPER Bepi psst").await;
+    bepi.read_until("WHISPER FROM Carlo: psst").await;
+    carlo.send("WHISPER Dora psst").await;
+    carlo.read_until("ERROR: user not found").await;
+    carlo.send("WHO").await;
+    let lines = carlo.lines_until("WHO END").await;
+    assert!(lines.iter().all(|line| !line.starts_with("WHISPER")));
+
+    // Once muted, Anna reaches Bepi neither at the table nor privately
+    bepi.send("MUTE Anna").await;
+    bepi.read_until("MUTED Anna").await;
+    anna.send("TABLE SAY still there?").await;
+    anna.read_until("TABLE SAY FROM Anna: still there?").await;
+    anna.send("WHISPER Bepi hello?").await;
+    // The lobby is done with the whisper once it answered Anna's next command
+    anna.send("WHO").await;
+    anna.read_until("WHO END").await;
+    bepi.send("WHO").await;
+    let lines = bepi.lines_until("WHO END").await;
+    assert!(lines.iter().all(|line| !line.contains("FROM Anna")));
+
+    server.stop().await;
+}
+
+#[tokio::test]
+async fn who_lists_the_users_and_where_they_are() {
+    let server = start_server().await;
+    let (mut anna, mut bepi) = anna_and_bepi(&server).await;
+    // Not counted until HELLO
+    let _anonymous = Client::connect(&server).await;
+
+    bepi.send("TABLE NEW \"Who\" 2 11").await;
+    let joined = bepi.read_until("TABLE JOINED").await;
+    let table_id = joined.trim_start_matches("TABLE JOINED ").to_string();
+
+    anna.send("WHO").await;
+    let lines = anna.lines_until("WHO END").await;
+    let start = lines.iter().position(|line| line == "WHO START").unwrap();
+    assert_eq!(
+        lines[start..],
+        [
+            "WHO START".to_string(),
+            "LOBBY Anna".to_string(),
+            format!("WAITING {} Bepi", table_id),
+            "WHO END".to_string(),
+        ]
+    );
+
+    server.stop().await;
+}
+
+#[tokio::test]
+async fn users_coming_and_going_are_told_only_with_presence() {
+    for presence in [false, true] {
+        let server = start_server_with(ServerConfig {
+            presence,
+            ..ServerConfig::default()
+        })
+        .await;
+        let (mut anna, mut bepi) = anna_and_bepi(&server).await;
+        bepi.send("HELLO Beppe").await;
+        bepi.read_until("HI Beppe").await;
+        bepi.send("QUIT").await;
+
+        // Asking WHO until Beppe is gone, to be sure the lobby saw the QUIT
+        let mut lines = Vec::new();
+        loop {
+            anna.send("WHO").await;
+            lines.extend(anna.lines_until("WHO END").await);
+            let who = lines.iter().rposition(|line| line == "WHO START").unwrap();
+            if !lines[who..].iter().any(|line| line.ends_with(" Beppe")) {
+                break;
+            }
+        }
+        let news: Vec<&str> = lines
+            .iter()
+            .map(String::as_str)
+            .filter(|line| line.starts_with("USER "))
+            .collect();
+        if presence {
+            assert_eq!(
+                news,
+                [
+                    "USER JOINED Anna",
+                    "USER JOINED Bepi",
+                    "USER LEFT Bepi",
+                    "USER JOINED Beppe",
+                    "USER LEFT Beppe",
+                ]
+            );
+        } else {
+            assert!(news.is_empty());
+        }
+
+        server.stop().await;
+    }
+}
+
+#[tokio::test]
+async fn tables_take_the_lowest_free_id() {
+    let server = start_server().await;
+    let (mut anna, mut bepi) = anna_and_bepi(&server).await;
+    anna.send("TABLE NEW \"First\" 2 11").await;
+    assert_eq!(anna.read_until("TABLE JOINED").await, "TABLE JOINED 1");
+    bepi.send("TABLE NEW \"Second\" 2 11").await;
+    assert_eq!(bepi.read_until("TABLE JOINED").await, "TABLE JOINED 2");
+
+    anna.send("TABLE LEAVE").await;
+    anna.read_until("TABLE LEAVED 1").await;
+    // The empty table closes on its own, freeing its id
+    loop {
+        anna.send("TABLE LIST").await;
+        anna.read_until("TABLE LIST START").await;
+        let tables = anna.lines_until("TABLE LIST END").await;
+        if !tables.iter().any(|table| table.starts_with("1 ")) {
+            break;
+        }
+    }
+    anna.send("TABLE NEW \"Third\" 2 11").await;
+    assert_eq!(anna.read_until("TABLE JOINED").await, "TABLE JOINED 1");
+
+    server.stop().await;
+}
+
+#[tokio::test]
+async fn saved_games_are_resumed_after_a_restart() {
+    let state_file =
+        std::env::temp_dir().join(format!("cirulla-state-{}.json", std::process::id()));
+    let config = ServerConfig {
+        state_file: Some(state_file.clone()),
+        ..ServerConfig::default()
+    };
+
+    let server = start_server_with(config.clone()).await;
+    let (mut anna, mut bepi) = anna_and_bepi(&server).await;
+    anna.send("TABLE NEW \"Saved\" 2 11").await;
+    let joined = anna.read_until("TABLE JOINED").await;
+    let table_id = joined.trim_start_matches("TABLE JOINED ").to_string();
+    bepi.send(&format!("TABLE JOIN {}", table_id)).await;
+    let mut statuses = [None, None];
+    let mut turns = Vec::new();
+    for (player, status) in [&mut anna, &mut bepi].into_iter().zip(statuses.iter_mut()) {
+        player.read_until("GAME START").await;
+        turns.push(player.read_turn(status).await);
+    }
+    let playing = turns.iter().position(|turn| turn == "PLAY").unwrap();
+    let hand_size = statuses[playing].as_ref().unwrap()["hand"]
+        .as_array()
+        .unwrap()
+        .len();
+    server.stop().await;
+    assert!(state_file.exists());
+
+    let server = start_server_with(config).await;
+    let (mut anna, mut bepi) = anna_and_bepi(&server).await;
+    anna.send("TABLE LIST").await;
+    anna.read_until("TABLE LIST START").await;
+    anna.expect(&format!("{} \"Saved\" 2/2 11", table_id)).await;
+    anna.send(&format!("TABLE JOIN {}", table_id)).await;
+    anna.read_until("TABLE JOINED").await;
+
+    // Leaving before the others are back does not end the saved game
+    anna.send("QUIT").await;
+    let mut anna = Client::connect(&server).await;
+    loop {
+        // The name is free again once the lobby handled the QUIT
+        anna.send("HELLO Anna").await;
+        match anna.read().await.as_deref() {
+            Some("HI Anna") => break,
+            Some("ERROR: name already in use") => {}
+            other => panic!("Unexpected answer {:?}", other),
+        }
+    }
+    anna.send(&format!("TABLE JOIN {}", table_id)).await;
+    anna.read_until("TABLE JOINED").await;
+    bepi.send(&format!("TABLE JOIN {}", table_id)).await;
+    bepi.read_until("TABLE JOINED").await;
+
+    let mut statuses = [None, None];
+    let mut resumed = Vec::new();
+    for (player, status) in [&mut anna, &mut bepi].into_iter().zip(statuses.iter_mut()) {
+        player.read_until("GAME START").await;
+        resumed.push(player.read_turn(status).await);
+    }
+    assert_eq!(resumed, turns);
+    let hand = statuses[playing].as_ref().unwrap()["hand"]
+        .as_array()
+        .unwrap();
+    assert_eq!(hand.len(), hand_size);
+
+    server.stop().await;
+    let _ = std::fs::remove_file(&state_file);
+}
+
+#[tokio::test]
+async fn admins_silence_and_kick() {
+    let server = start_server_with(ServerConfig {
+        admin_password: Some("secret".to_string()),
+        ..ServerConfig::default()
+    })
+    .await;
+    let (mut anna, mut bepi) = anna_and_bepi(&server).await;
+
+    bepi.send("SILENCE Anna").await;
+    bepi.expect("ERROR: you are not an admin").await;
+    bepi.send("ADMIN guessed").await;
+    bepi.expect("ERROR: wrong password").await;
+    bepi.send("ADMIN secret").await;
+    bepi.expect("ADMIN OK").await;
+
+    bepi.send("SILENCE Anna").await;
+    bepi.expect("SILENCED Anna").await;
+    anna.expect("SILENCED Anna").await;
+    anna.send("SCREAM hello?").await;
+    anna.expect("ERROR: you have been silenced").await;
+    bepi.send("UNSILENCE Anna").await;
+    bepi.expect("UNSILENCED Anna").await;
+    anna.expect("UNSILENCED Anna").await;
+    anna.send("SCREAM hello!").await;
+    anna.expect("SCREAM FROM Anna: hello!").await;
+    bepi.expect("SCREAM FROM Anna: hello!").await;
+
+    bepi.send("KICK Anna").await;
+    bepi.expect("KICKED Anna").await;
+    anna.expect("KICKED Anna").await;
+    assert_eq!(anna.read().await, None);
+
+    server.stop().await;
+}
+
+#[tokio::test]
+async fn binding_a_busy_address_fails() {
+    let server = start_server().await;
+    let config = ServerConfig {
+        listen: vec![server.local_addrs()[0].to_string()],
+        ..ServerConfig::default()
+    };
+    assert!(Server::bind(config, None).await.is_err());
+    server.stop().await;
+}
+
+#[cfg(feature = "metrics")]
+#[tokio::test]
+async fn metrics_are_served_over_http() {
+    use tokio::io::AsyncReadExt;
+
+    let config = ServerConfig {
+        listen: vec!["127.0.0.1:0".to_string()],
+        metrics: Some("127.0.0.1:0".to_string()),
+        ..ServerConfig::default()
+    };
+    let server = Server::bind(config, None).await.unwrap().spawn();
+    let mut anna = Client::connect(&server).await;
+    anna.send("HELLO Anna").await;
+    anna.expect("HI Anna").await;
+    anna.send("DANCE").await;
+    anna.expect("ERROR: invalid command").await;
+    anna.send("TABLE NEW \"Alone\" 2 11").await;
+    anna.read_until("TABLE JOINED").await;
+    anna.send("PLAY 7d").await;
+    anna.read_until("ERROR").await;
+
+    let mut http = TcpStream::connect(server.metrics_addr().unwrap())
+        .await
+        .unwrap();
+    http.write_all(b"GET /metrics HTTP/1.1\r\nHost: localhost\r\n\r\n")
+        .await
+        .unwrap();
+    let mut response = String::new();
+    http.read_to_string(&mut response).await.unwrap();
+
+    assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+    assert!(response.contains("\ncirulla_sessions 1\n"));
+    assert!(response.contains("\ncirulla_commands_total{command=\"hello\"} 1\n"));
+    assert!(response.contains("\ncirulla_errors_total{error=\"invalid_command\"} 1\n"));
+    // Rejected plays are not moves
+    assert!(response.contains("\ncirulla_move_latency_seconds_count 0\n"));
+
+    server.stop().await;
+}
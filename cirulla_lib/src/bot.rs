@@ -0,0 +1,231 @@
+use crate::{Card, Game, NextAction};
+use rand::{rngs::StdRng, seq::SliceRandom, Rng, SeedableRng};
+
+/// A computer player.
+pub trait Bot {
+    /// Chooses the card the current player of `game` plays. It must be asked
+    /// only on the bot's own turn, with cards in its hand.
+    fn choose_card(&mut self, game: &Game) -> String;
+}
+
+/// Plays the card that takes the most right now, without looking ahead.
+pub struct Greedy;
+
+impl Greedy {
+    /// How good playing `card` is for the current player, judging only by
+    /// what it takes and by what it leaves to the next player.
+    fn score(game: &Game, card: &Card) -> i32 {
+        let mut after = game.clone();
+        let index = after.current_player_index;
+        let before = &game.players[index];
+        after
+            .player_play(&card.to_string())
+            .expect("The card comes from the hand");
+        let player = &after.players[index];
+
+        let taken = &player.catched[before.catched.len()..];
+        if taken.is_empty() {
+            // Nothing taken: keep the good cards and leave no easy broom
+            let left: u8 = after.table.iter().map(|c| c.value()).sum();
+            let sweepable = if left <= 14 { 5 } else { 0 };
+            return -sweepable - i32::from(card.primiera_value()) / 4;
+        }
+
+        let mut score = taken.len() as i32;
+        score += 20 * i32::from(player.brooms - before.brooms);
+        for card in taken {
+            match card {
+                Card::Diamond(7) => score += 10,
+                Card::Diamond(_) => score += 2,
+                _ => {}
+            }
+            if card.value() == 7 {
+                score += 2;
+            }
+        }
+        score
+    }
+}
+
+impl Bot for Greedy {
+    fn choose_card(&mut self, game: &Game) -> String {
+        game.current_player()
+            .hand
+            .iter()
+            .max_by_key(|card| Greedy::score(game, card))
+            .expect("The current player has cards to play")
+            .to_string()
+    }
+}
+
+/// Monte Carlo search over the bot's own choices: every iteration guesses
+/// the cards it cannot see, tries the most promising card according to UCB1
+/// and plays the rest of the hand out at random.
+pub struct Mcts {
+    iterations: usize,
+    rng: StdRng,
+}
+
+impl Mcts {
+    pub fn new(iterations: usize) -> Mcts {
+        Mcts {
+            iterations,
+            rng: StdRng::from_entropy(),
+        }
+    }
+
+    /// A game as it could be, given what the current player knows: the
+    /// hidden hands and the deck are dealt again from the cards they hold.
+    fn determinize(&mut self, game: &Game) -> Game {
+        let mut guess = game.clone();
+        let me = guess.current_player_index;
+        let mut unknown = std::mem::take(&mut guess.deck);
+        for (index, player) in guess.players.iter_mut().enumerate() {
+            if index != me && !player.hand_visible {
+                unknown.append(&mut player.hand);
+            }
+        }
+        unknown.shuffle(&mut self.rng);
+
+        for (index, player) in guess.players.iter_mut().enumerate() {
+            if index != me && !player.hand_visible {
+                let size = game.players[index].hand.len();
+                player.hand = unknown.split_off(unknown.len() - size);
+            }
+        }
+        guess.deck = unknown;
+        guess
+    }
+
+    /// Plays `card` and then random cards up to the end of the hand, giving
+    /// back how well it went for `player_id`, from 0 to 1.
+    fn playout(&mut self, mut game: Game, card: &str, player_id: &str) -> f64 {
+        let before: Vec<u8> = game.players.iter().map(|p| p.points).collect();
+        let ids: Vec<String> = game.players.iter().map(|p| p.id.clone()).collect();
+
+        let mut card = card.to_string();
+        loop {
+            game.player_play(&card)
+                .expect("The card comes from the hand");
+            match game.next_round_action() {
+                NextAction::NextPlayer => {}
+                NextAction::NextRound => game.start_round().expect("The hand is running"),
+                NextAction::EndHand => break,
+            }
+            let hand = &game.current_player().hand;
+            card = hand[self.rng.gen_range(0..hand.len())].to_string();
+        }
+        if game.end_hand().is_err() {
+            return 0.5;
+        }
+
+        let gained = |id: &String| -> i32 {
+            let start = ids.iter().position(|i| i == id).map(|i| before[i]);
+            let end = game.players.iter().find(|p| &p.id == id).map(|p| p.points);
+            i32::from(end.unwrap_or(0)) - i32::from(start.unwrap_or(0))
+        };
+        let mine = ids.iter().find(|id| *id == player_id).map(gained);
+        let best_other = ids
+            .iter()
+            .filter(|id| *id != player_id)
+            .map(gained)
+            .max()
+            .unwrap_or(0);
+        let margin = (mine.unwrap_or(0) - best_other).clamp(-10, 10);
+        0.5 + f64::from(margin) / 20.0
+    }
+}
+
+impl Default for Mcts {
+    fn default() -> Self {
+        Mcts::new(1000)
+    }
+}
+
+impl Bot for Mcts {
+    fn choose_card(&mut self, game: &Game) -> String {
+        let player = game.current_player();
+        let cards: Vec<String> = player.hand.iter().map(|c| c.to_string()).collect();
+        let first = cards.first().expect("The current player has cards to play");
+        if cards.len() == 1 {
+            return first.clone();
+        }
+
+        let mut visits = vec![0u32; cards.len()];
+        let mut rewards = vec![0f64; cards.len()];
+        for iteration in 1..=self.iterations {
+            let choice = match visits.iter().position(|v| *v == 0) {
+                Some(unvisited) => unvisited,
+                None => (0..cards.len())
+                    .max_by(|a, b| {
+                        let ucb = |i: usize| {
+                            rewards[i] / f64::from(visits[i])
+                                + (2.0 * (iteration as f64).ln() / f64::from(visits[i])).sqrt()
+                        };
+                        ucb(*a).total_cmp(&ucb(*b))
+                    })
+                    .unwrap_or(0),
+            };
+            let guess = self.determinize(game);
+            rewards[choice] += self.playout(guess, &cards[choice], &player.id);
+            visits[choice] += 1;
+        }
+
+        let best = (0..cards.len()).max_by_key(|i| visits[*i]).unwrap_or(0);
+        cards[best].clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn game_with(hand: Vec<Card>, table: Vec<Card>) -> Game {
+        let mut game = Game::new(51);
+        game.add_player("Anna", None).unwrap();
+        game.add_player("Bepi", None).unwrap();
+        game.start_game().unwrap();
+        game.start_hand().unwrap();
+
+        // Bepi holds three cards and the deck is left with whole rounds;
+        // the rest has already been taken
+        let mut rest = Game::new(51).deck;
+        game.players[0].catched.clear();
+        game.players[0].brooms = 0;
+        rest.retain(|c| !hand.contains(c) && !table.contains(c));
+        game.players[1].hand = rest.split_off(rest.len() - 3);
+        game.players[1].catched = rest.drain(..rest.len() % 6).collect();
+        game.players[0].hand = hand;
+        game.table = table;
+        game.deck = rest;
+        game
+    }
+
+    #[test]
+    fn greedy_takes_the_pretty_seven() {
+        let game = game_with(
+            vec![Card::Club(2), Card::Spade(7), Card::Heart(4)],
+            vec![Card::Diamond(7), Card::Club(10)],
+        );
+        assert_eq!(Greedy.choose_card(&game), "7s");
+    }
+
+    #[test]
+    fn greedy_sweeps_the_table() {
+        let game = game_with(
+            vec![Card::Club(3), Card::Spade(6), Card::Heart(5)],
+            vec![Card::Diamond(2), Card::Club(4)],
+        );
+        assert_eq!(Greedy.choose_card(&game), "6s");
+    }
+
+    #[test]
+    fn mcts_plays_a_card_it_holds() {
+        let game = game_with(
+            vec![Card::Club(3), Card::Spade(6), Card::Heart(9)],
+            vec![Card::Diamond(2), Card::Club(4)],
+        );
+        let card = Mcts::new(50).choose_card(&game);
+        assert!(["3c", "6s", "Qh"].contains(&card.as_str()));
+    }
+}
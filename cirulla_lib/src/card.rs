@@ -1,7 +1,7 @@
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use std::fmt::Display;
 
-#[derive(Clone, Copy, PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum Card {
     Heart(u8),
     Diamond(u8),
@@ -22,7 +22,7 @@ impl Card {
     pub fn primiera_value(&self) -> u8 {
         match self.value() {
             1 => 13,
-            8 | 9 | 10 => 1,
+            8..=10 => 1,
             v => v * 2,
         }
     }
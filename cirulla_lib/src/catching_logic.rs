@@ -2,57 +2,80 @@ use itertools::Itertools;
 
 use crate::{Card, Player};
 
-pub fn catching_logic(
-    table: &mut Vec<Card>,
-    player: &mut Player,
-    card: Card,
-    can_broom: bool,
-) -> bool {
+/// Every set of table cards that `card` can take, among which the player
+/// chooses; when there is none the card stays on the table.
+pub fn capture_options(table: &[Card], card: Card) -> Vec<Vec<Card>> {
     let ace_on_table = table.iter().any(|c| c.value() == 1);
 
     // Scopa d'assi
-    if !ace_on_table && card.value() == 1 && table.len() != 0 {
-        while let Some(c) = table.pop() {
-            player.catch(c);
-        }
-        player.catch(card);
-        if can_broom {
-            player.increment_brooms(1);
-        }
-        return true;
+    if !ace_on_table && card.value() == 1 && !table.is_empty() {
+        return vec![table.to_vec()];
     }
 
-    // Presa o ciapachinze
+    // Presa o ciapachinze: the most cards must be taken
     for k in (1..table.len() + 1).rev() {
-        // The permutation() method needs to clone the cards, so we need to work on a copy of the table
-        let working_cards = table.iter().map(|c| c.clone());
-        for permut in working_cards.permutations(k) {
-            let mut value_total = 0;
-            permut.iter().for_each(|c| value_total += c.value());
-            if value_total == card.value() || value_total + card.value() == 15 {
-                for c in permut {
-                    if let Some(key) = table.iter().position(|x| *x == c) {
-                        player.catch(table.remove(key));
-                    }
-                }
-                player.catch(card);
-                if can_broom && table.is_empty() {
-                    player.increment_brooms(1);
-                }
-                return true;
-            }
+        let options: Vec<Vec<Card>> = table
+            .iter()
+            .copied()
+            .combinations(k)
+            .filter(|set| {
+                let value_total: u8 = set.iter().map(|c| c.value()).sum();
+                value_total == card.value() || value_total + card.value() == 15
+            })
+            .collect();
+        if !options.is_empty() {
+            return options;
         }
     }
 
-    table.push(card);
+    Vec::new()
+}
 
-    false
+/// Plays `card` taking `capture`, one of its [capture_options], from the
+/// table; an empty capture leaves the card on the table.
+pub fn capture(
+    table: &mut Vec<Card>,
+    player: &mut Player,
+    card: Card,
+    capture: &[Card],
+    can_broom: bool,
+) -> bool {
+    if capture.is_empty() {
+        table.push(card);
+        return false;
+    }
+
+    for c in capture {
+        if let Some(key) = table.iter().position(|x| x == c) {
+            player.catch(table.remove(key));
+        }
+    }
+    player.catch(card);
+    if can_broom && table.is_empty() {
+        player.increment_brooms(1);
+    }
+    true
 }
 
 #[cfg(test)]
+#[allow(clippy::bool_assert_comparison)]
 mod tests {
     use super::*;
 
+    /// The play the game makes when the player does not choose.
+    fn catching_logic(
+        table: &mut Vec<Card>,
+        player: &mut Player,
+        card: Card,
+        can_broom: bool,
+    ) -> bool {
+        let first = capture_options(table, card)
+            .into_iter()
+            .next()
+            .unwrap_or_default();
+        capture(table, player, card, &first, can_broom)
+    }
+
     fn table(values: &[u8]) -> Vec<Card> {
         values.iter().map(|v| Card::Heart(*v)).collect()
     }
@@ -264,4 +287,58 @@ mod tests {
         assert_eq!(player.catched.len(), 0);
         assert_eq!(player.brooms, 0);
     }
+
+    #[test]
+    fn options_are_all_the_biggest_catches() {
+        let table = vec![
+            Card::Heart(2),
+            Card::Club(5),
+            Card::Spade(3),
+            Card::Diamond(4),
+        ];
+        let options = capture_options(&table, Card::Heart(7));
+        assert_eq!(options.len(), 3);
+        assert!(options.iter().all(|set| set.len() == 2));
+        assert!(capture_options(&[Card::Club(10)], Card::Heart(3)).is_empty());
+    }
+
+    #[test]
+    fn chosen_capture_is_taken() {
+        let mut table = vec![Card::Heart(2), Card::Club(3), Card::Spade(2)];
+        let mut player = Player::new("Test", None);
+
+        assert!(capture(
+            &mut table,
+            &mut player,
+            Card::Heart(5),
+            &[Card::Spade(2), Card::Club(3)],
+            true
+        ));
+        assert_eq!(table, vec![Card::Heart(2)]);
+        assert_eq!(player.catched.len(), 3);
+        assert_eq!(player.brooms, 0);
+    }
+
+    #[test]
+    fn game_refuses_a_capture_that_is_not_an_option() {
+        let mut game = crate::Game::new(51);
+        game.add_player("Anna", None).unwrap();
+        game.add_player("Bepi", None).unwrap();
+        game.start_game().unwrap();
+        game.start_hand().unwrap();
+        game.players[0].hand = vec![Card::Heart(7)];
+        game.table = vec![
+            Card::Heart(2),
+            Card::Club(5),
+            Card::Spade(3),
+            Card::Diamond(4),
+        ];
+
+        assert!(game
+            .player_play_capturing("7h", &[Card::Heart(2), Card::Spade(3)])
+            .is_err());
+        game.player_play_capturing("7h", &[Card::Spade(3), Card::Diamond(4)])
+            .unwrap();
+        assert_eq!(game.table, vec![Card::Heart(2), Card::Club(5)]);
+    }
 }
@@ -11,6 +11,8 @@ pub enum GameError {
     DeckNotReady,
     HandNotStarted,
     CardNotFound,
+    InvalidSeed,
+    InvalidCapture,
 }
 
 impl Display for GameError {
@@ -25,6 +27,8 @@ impl Display for GameError {
             GameError::DeckNotReady => write!(f, "Deck not ready"),
             GameError::HandNotStarted => write!(f, "Hand not started"),
             GameError::CardNotFound => write!(f, "Card not found"),
+            GameError::InvalidSeed => write!(f, "Invalid seed"),
+            GameError::InvalidCapture => write!(f, "Invalid capture"),
         }
     }
 }
@@ -0,0 +1,64 @@
+use crate::{Card, Effect};
+use serde::Serialize;
+
+/// Something that happened at the table, for whoever follows the game: the
+/// game keeps them until they are [taken](crate::Game::take_events).
+#[derive(Clone, Debug, Serialize)]
+pub enum GameEvent {
+    /// A hand was dealt, the deck being held by `dealer`
+    HandStarted { dealer: String },
+    /// A player was credited with an effect: a knock or a broom at the deal
+    Effect { player_id: String, effect: Effect },
+    /// A card was played, taking `taken` from the table
+    Played {
+        player_id: String,
+        card: Card,
+        taken: Vec<Card>,
+        broom: bool,
+    },
+    /// At the end of the hand the cards left on the table go to the last
+    /// player who took something
+    LastTake { player_id: String, cards: Vec<Card> },
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Game, NextAction};
+
+    #[test]
+    fn plays_are_told_with_what_they_took() {
+        let mut game = Game::new(51);
+        game.add_player("Anna", None).unwrap();
+        game.add_player("Bepi", None).unwrap();
+        game.start_game().unwrap();
+        game.start_hand().unwrap();
+        assert!(matches!(
+            game.take_events()[0],
+            GameEvent::HandStarted { ref dealer } if *dealer == game.players[0].id
+        ));
+
+        game.players[0].hand = vec![Card::Heart(7), Card::Club(9)];
+        game.table = vec![Card::Spade(3), Card::Diamond(4)];
+        game.player_play("7h").unwrap();
+        game.next_round_action();
+        let events = game.take_events();
+        assert!(matches!(
+            &events[..],
+            [GameEvent::Played { card: Card::Heart(7), taken, broom: true, .. }] if taken.len() == 2
+        ));
+
+        game.deck.clear();
+        game.players[1].hand = vec![Card::Spade(2)];
+        game.player_play("2s").unwrap();
+        assert!(matches!(game.next_round_action(), NextAction::NextPlayer));
+        game.player_play("Qc").unwrap();
+        assert!(matches!(game.next_round_action(), NextAction::EndHand));
+        let events = game.take_events();
+        assert!(matches!(
+            events.last(),
+            Some(GameEvent::LastTake { cards, .. }) if cards.len() == 2
+        ));
+        assert!(game.take_events().is_empty());
+    }
+}
@@ -1,11 +1,11 @@
 use crate::{
     card::Card,
-    catching_logic::catching_logic,
+    catching_logic::{capture, capture_options},
     player::{ComparativePoints, Effect, Player},
-    GameError,
+    GameError, GameEvent, Seed,
 };
 use rand::seq::SliceRandom;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
 #[derive(Clone, Serialize)]
 pub struct PlayerForPlayer {
@@ -30,6 +30,7 @@ pub struct GameForPlayer {
     pub active_payer: usize,
 }
 
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Game {
     pub deck: Vec<Card>,
     pub players: Vec<Player>,
@@ -39,6 +40,15 @@ pub struct Game {
     pub current_player_index: usize,
     last_player_caught: usize,
     pub win_at: u8,
+    #[serde(skip)]
+    events: Vec<GameEvent>,
+}
+
+/// The cards dealt in a hand: those put on the table and, for each player,
+/// the ones received over all its rounds.
+pub struct Deal {
+    pub table: Vec<Card>,
+    pub hands: Vec<Vec<Card>>,
 }
 
 #[derive(Clone, Serialize)]
@@ -73,6 +83,7 @@ impl Game {
             current_player_index: 0,
             last_player_caught: 1000,
             win_at,
+            events: Vec::new(),
         }
     }
 
@@ -110,6 +121,11 @@ impl Game {
         }
     }
 
+    /// What happened since the last time the events were taken.
+    pub fn take_events(&mut self) -> Vec<GameEvent> {
+        std::mem::take(&mut self.events)
+    }
+
     pub fn current_player(&self) -> &Player {
         self.players.get(self.current_player_index).unwrap()
     }
@@ -168,6 +184,12 @@ impl Game {
     }
 
     pub fn start_hand(&mut self) -> Result<(), GameError> {
+        self.start_hand_with_seed(&Seed::random())
+    }
+
+    /// Starts a hand shuffling the deck from `seed`: the same seed always
+    /// deals the same cards.
+    pub fn start_hand_with_seed(&mut self, seed: &Seed) -> Result<(), GameError> {
         if !self.game_started {
             return Err(GameError::GameNotStarted);
         }
@@ -179,8 +201,11 @@ impl Game {
             player.start_hand();
         }
 
+        // The order the cards were collected in must not matter
+        self.deck.sort_by_key(|card| (card.suit(), card.value()));
+        let mut rng = seed.rng();
         loop {
-            self.deck.shuffle(&mut rand::thread_rng());
+            self.deck.shuffle(&mut rng);
             let mut aces = 0;
             for _ in 0..4 {
                 let card = self.deck.pop().unwrap();
@@ -198,6 +223,10 @@ impl Game {
             }
         }
 
+        self.events.push(GameEvent::HandStarted {
+            dealer: self.players[0].id.clone(),
+        });
+
         let total_points = self.table.iter().fold(0, |acc, c| acc + c.value());
 
         if total_points == 15 || total_points == 30 {
@@ -207,7 +236,11 @@ impl Game {
             self.players[0].increment_brooms(total_points / 15);
             self.players[0]
                 .effect
-                .push(Effect::DeckHandlerBroom(total_points))
+                .push(Effect::DeckHandlerBroom(total_points));
+            self.events.push(GameEvent::Effect {
+                player_id: self.players[0].id.clone(),
+                effect: Effect::DeckHandlerBroom(total_points),
+            });
         }
 
         self.hand_started = true;
@@ -215,6 +248,28 @@ impl Game {
         Ok(())
     }
 
+    /// Deals a whole hand from `seed` to `players` players, as
+    /// [start_hand_with_seed](Game::start_hand_with_seed) would.
+    pub fn replay_deal(seed: &Seed, players: usize) -> Result<Deal, GameError> {
+        let mut game = Game::new(0);
+        for index in 0..players {
+            game.add_player(&format!("Player {}", index + 1), None)?;
+        }
+        game.start_game()?;
+        game.start_hand_with_seed(seed)?;
+
+        let table = game.table.clone();
+        let mut hands = vec![Vec::new(); players];
+        while !game.deck.is_empty() {
+            game.start_round()?;
+            for (hand, player) in hands.iter_mut().zip(game.players.iter_mut()) {
+                hand.append(&mut player.hand);
+            }
+        }
+
+        Ok(Deal { table, hands })
+    }
+
     pub fn end_hand(&mut self) -> Result<HandResult, GameError> {
         if !self.hand_started {
             return Err(GameError::HandNotStarted);
@@ -332,7 +387,14 @@ impl Game {
             return Err(GameError::HandNotStarted);
         }
         for player in self.players.iter_mut() {
+            let effects = player.effect.len();
             player.draw(&mut self.deck);
+            for effect in &player.effect[effects..] {
+                self.events.push(GameEvent::Effect {
+                    player_id: player.id.clone(),
+                    effect: effect.clone(),
+                });
+            }
         }
 
         self.current_player_index = 0;
@@ -341,13 +403,55 @@ impl Game {
     }
 
     pub fn player_play(&mut self, card: &str) -> Result<(), GameError> {
+        let capture = self
+            .capture_options(card)?
+            .into_iter()
+            .next()
+            .unwrap_or_default();
+        self.player_play_capturing(card, &capture)
+    }
+
+    /// The sets of table cards the current player can take with `card`;
+    /// empty when the card can take nothing.
+    pub fn capture_options(&self, card: &str) -> Result<Vec<Vec<Card>>, GameError> {
+        let card = self
+            .current_player()
+            .hand
+            .iter()
+            .find(|c| c.to_string().eq_ignore_ascii_case(card))
+            .ok_or(GameError::CardNotFound)?;
+        Ok(capture_options(&self.table, *card))
+    }
+
+    /// Plays `card` taking `taken` from the table, which must be one of its
+    /// [capture options](Game::capture_options).
+    pub fn player_play_capturing(&mut self, card: &str, taken: &[Card]) -> Result<(), GameError> {
+        let options = self.capture_options(card)?;
+        let valid = if options.is_empty() {
+            taken.is_empty()
+        } else {
+            options.iter().any(|option| {
+                option.len() == taken.len() && option.iter().all(|c| taken.contains(c))
+            })
+        };
+        if !valid {
+            return Err(GameError::InvalidCapture);
+        }
+
         let player = self.players.get_mut(self.current_player_index).unwrap();
         let can_broom = !self.deck.is_empty();
 
         match player.give_card_from_hand(card) {
             None => Err(GameError::CardNotFound),
             Some(card) => {
-                let caught = catching_logic(&mut self.table, player, card, can_broom);
+                let brooms = player.brooms;
+                let caught = capture(&mut self.table, player, card, taken, can_broom);
+                self.events.push(GameEvent::Played {
+                    player_id: player.id.clone(),
+                    card,
+                    taken: taken.to_vec(),
+                    broom: player.brooms > brooms,
+                });
                 if caught {
                     self.last_player_caught = self.current_player_index;
                 }
@@ -371,6 +475,12 @@ impl Game {
         if self.current_player().hand.is_empty() {
             return if self.deck.is_empty() {
                 let last_catcher = self.players.get_mut(self.last_player_caught).unwrap();
+                if !self.table.is_empty() {
+                    self.events.push(GameEvent::LastTake {
+                        player_id: last_catcher.id.clone(),
+                        cards: self.table.clone(),
+                    });
+                }
                 while let Some(card) = self.table.pop() {
                     last_catcher.catched.push(card);
                 }
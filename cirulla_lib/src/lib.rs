@@ -1,11 +1,19 @@
+mod bot;
 mod card;
 mod catching_logic;
 mod error;
+mod event;
 mod game;
 mod player;
+mod seed;
 
+pub use bot::Bot;
+pub use bot::Greedy;
+pub use bot::Mcts;
 pub use card::Card;
 pub use error::GameError;
+pub use event::GameEvent;
+pub use game::Deal;
 pub use game::Game;
 pub use game::GameForPlayer;
 pub use game::HandResult;
@@ -13,3 +21,4 @@ pub use game::NextAction;
 pub use game::PlayerForPlayer;
 pub use player::Effect;
 pub use player::Player;
+pub use seed::Seed;
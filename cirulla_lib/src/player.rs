@@ -1,10 +1,10 @@
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
 use crate::card::Card;
 use std::fmt::Display;
 
-#[derive(Clone, Serialize)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub enum Effect {
     Knocked(u8),
     DeckHandlerBroom(u8),
@@ -48,6 +48,7 @@ impl PrimieraEvaluation {
     }
 }
 
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Player {
     pub id: String,
     pub name: String,
@@ -85,7 +86,7 @@ impl Player {
         let mut cards_taken = Vec::new();
 
         for card in self.catched.iter() {
-            cards_taken.push(card.clone());
+            cards_taken.push(*card);
             primiera.check_card(card);
             if let Card::Diamond(v) = card {
                 all_diamonds.push(*v);
@@ -0,0 +1,122 @@
+use crate::GameError;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha20Rng;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use sha2::{Digest, Sha256};
+use std::{fmt::Display, str::FromStr};
+
+/// What a hand is shuffled from.
+///
+/// A server announces the [commitment](Seed::commitment) before dealing and
+/// reveals the seed once the hand is over, so that players can replay the
+/// deal and check that the deck was not stacked.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Seed([u8; 32]);
+
+impl Seed {
+    pub fn random() -> Seed {
+        Seed(rand::thread_rng().gen())
+    }
+
+    /// SHA-256 of the seed, in hex.
+    pub fn commitment(&self) -> String {
+        to_hex(&Sha256::digest(self.0))
+    }
+
+    /// The seed combined with the entropy contributed by the players, in
+    /// the given order.
+    pub fn mix(&self, entropy: &[String]) -> Seed {
+        let mut hasher = Sha256::new();
+        hasher.update(self.0);
+        for contribution in entropy {
+            hasher.update((contribution.len() as u32).to_be_bytes());
+            hasher.update(contribution.as_bytes());
+        }
+        Seed(hasher.finalize().into())
+    }
+
+    pub(crate) fn rng(&self) -> ChaCha20Rng {
+        ChaCha20Rng::from_seed(self.0)
+    }
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+impl Display for Seed {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", to_hex(&self.0))
+    }
+}
+
+impl FromStr for Seed {
+    type Err = GameError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.len() != 64 || !s.is_ascii() {
+            return Err(GameError::InvalidSeed);
+        }
+        let mut seed = [0u8; 32];
+        for (byte, pair) in seed.iter_mut().zip(s.as_bytes().chunks(2)) {
+            let pair = std::str::from_utf8(pair).map_err(|_| GameError::InvalidSeed)?;
+            *byte = u8::from_str_radix(pair, 16).map_err(|_| GameError::InvalidSeed)?;
+        }
+        Ok(Seed(seed))
+    }
+}
+
+impl Serialize for Seed {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+impl<'de> Deserialize<'de> for Seed {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Game;
+
+    #[test]
+    fn seeds_round_trip_through_hex() {
+        let seed = Seed::random();
+        assert_eq!(seed.to_string().parse::<Seed>().unwrap(), seed);
+        assert!("xyz".parse::<Seed>().is_err());
+        assert!("zz".repeat(32).parse::<Seed>().is_err());
+        assert_eq!(seed.commitment().len(), 64);
+        assert_ne!(seed.mix(&["ab".to_string()]), seed.mix(&["ba".to_string()]));
+    }
+
+    #[test]
+    fn a_seed_always_deals_the_same_cards() {
+        let seed = Seed::random();
+        let mut game = Game::new(51);
+        game.add_player("Anna", None).unwrap();
+        game.add_player("Bepi", None).unwrap();
+        game.start_game().unwrap();
+        game.start_hand_with_seed(&seed).unwrap();
+        game.start_round().unwrap();
+
+        let deal = Game::replay_deal(&seed, 2).unwrap();
+        assert_eq!(deal.table, game.table);
+        assert_eq!(deal.hands[0][..3], game.players[0].hand[..]);
+        assert_eq!(deal.hands[1][..3], game.players[1].hand[..]);
+        assert_eq!(deal.hands[0].len(), 18);
+
+        let other = Game::replay_deal(&Seed::random(), 2).unwrap();
+        assert_ne!(other.hands, deal.hands);
+    }
+}